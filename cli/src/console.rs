@@ -0,0 +1,71 @@
+//! The tester's console port, for sending commands.
+
+use std::{
+    fs::File,
+    io::{ErrorKind, Read, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::device;
+
+/// How long the tester may take to answer a command. It only handles them between two passes, but
+/// answers right away.
+const TIMEOUT: Duration = Duration::from_secs(2);
+const PROMPT: &str = "> ";
+
+pub struct Console {
+    port: File,
+}
+
+impl Console {
+    /// Opens the console at `path`, and waits for the tester to answer.
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut console = Self {
+            port: device::open(path)?,
+        };
+        // an empty line, to get a prompt and drop any half-typed input
+        console
+            .command("")
+            .context("the tester doesn't answer on the console")?;
+        Ok(console)
+    }
+
+    /// Sends `command` and returns the answer, fails if it's an error.
+    pub fn command(&mut self, command: &str) -> Result<String> {
+        write!(self.port, "{command}\r")?;
+
+        let reply = self.read_until_prompt()?;
+        // the tester echoes the command
+        let answer = reply
+            .strip_prefix(command)
+            .unwrap_or(&reply)
+            .trim_start_matches(['\r', '\n'])
+            .replace("\r\n", "\n");
+        if let Some(error) = answer.lines().find_map(|line| line.strip_prefix("error: ")) {
+            bail!("`{command}`: {error}");
+        }
+        Ok(answer)
+    }
+
+    /// Reads until the prompt for the next command, returns what came before it.
+    fn read_until_prompt(&mut self) -> Result<String> {
+        let deadline = Instant::now() + TIMEOUT;
+        let mut reply = Vec::new();
+        let mut buf = [0; 256];
+        while !reply.ends_with(PROMPT.as_bytes()) {
+            if Instant::now() >= deadline {
+                bail!("no answer from the tester");
+            }
+            match self.port.read(&mut buf) {
+                Ok(n) => reply.extend_from_slice(&buf[..n]),
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        reply.truncate(reply.len() - PROMPT.len());
+        Ok(String::from_utf8_lossy(&reply).into_owned())
+    }
+}
@@ -0,0 +1,128 @@
+//! Finding the testers among the USB serial ports, and opening their ports.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use nix::sys::termios::{self, SetArg, SpecialCharacterIndices};
+
+/// USB vendor and product ID of the tester
+const VID: &str = "16c0";
+const PID: &str = "27dd";
+/// First interface of the console's port, the event stream's port follows it
+const CONSOLE_INTERFACE: u8 = 0;
+const STREAM_INTERFACE: u8 = 2;
+
+/// A connected tester
+#[derive(Debug)]
+pub struct Device {
+    /// The USB device in sysfs, e.g. `/sys/devices/…/usb1/1-2`
+    pub usb_path: PathBuf,
+    pub console: Option<PathBuf>,
+    pub stream: Option<PathBuf>,
+}
+
+/// Lists the connected testers, found through sysfs.
+pub fn list() -> Result<Vec<Device>> {
+    let entries = match fs::read_dir("/sys/class/tty") {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).context("can't list the serial ports"),
+    };
+
+    let mut devices: Vec<Device> = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with("ttyACM") {
+            continue;
+        }
+
+        // the USB interface of the port, its parent is the USB device
+        let Ok(interface) = fs::canonicalize(entry.path().join("device")) else {
+            continue;
+        };
+        let Some(usb_path) = interface.parent() else {
+            continue;
+        };
+        if read_attr(usb_path, "idVendor").as_deref() != Some(VID)
+            || read_attr(usb_path, "idProduct").as_deref() != Some(PID)
+        {
+            continue;
+        }
+        let Some(number) = read_attr(&interface, "bInterfaceNumber")
+            .and_then(|number| u8::from_str_radix(&number, 16).ok())
+        else {
+            continue;
+        };
+
+        let index = match devices
+            .iter()
+            .position(|device| device.usb_path == usb_path)
+        {
+            Some(index) => index,
+            None => {
+                devices.push(Device {
+                    usb_path: usb_path.to_owned(),
+                    console: None,
+                    stream: None,
+                });
+                devices.len() - 1
+            }
+        };
+        let path = Path::new("/dev").join(name);
+        match number {
+            CONSOLE_INTERFACE => devices[index].console = Some(path),
+            STREAM_INTERFACE => devices[index].stream = Some(path),
+            _ => {}
+        }
+    }
+
+    devices.sort_by(|a, b| a.usb_path.cmp(&b.usb_path));
+    Ok(devices)
+}
+
+/// The console and stream ports of the only connected tester.
+pub fn find_ports() -> Result<(PathBuf, PathBuf)> {
+    let mut devices = list()?;
+    if devices.len() > 1 {
+        bail!("more than one tester found, select one with `--console` and `--stream`");
+    }
+    match devices.pop() {
+        Some(Device {
+            console: Some(console),
+            stream: Some(stream),
+            ..
+        }) => Ok((console, stream)),
+        Some(_) => bail!("the tester's ports are incomplete, is the firmware up to date?"),
+        None => bail!("no tester found"),
+    }
+}
+
+/// Reads a sysfs attribute of `dir`, without the newline.
+fn read_attr(dir: &Path, name: &str) -> Option<String> {
+    let value = fs::read_to_string(dir.join(name)).ok()?;
+    Some(value.trim_end().to_owned())
+}
+
+/// Opens the serial port at `path` in raw mode, with reads timing out after 100 ms.
+///
+/// Opening raises DTR, without which the tester doesn't send anything.
+pub fn open(path: &Path) -> Result<File> {
+    let port = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("can't open {}", path.display()))?;
+
+    let mut attrs = termios::tcgetattr(&port)
+        .with_context(|| format!("{} isn't a serial port", path.display()))?;
+    termios::cfmakeraw(&mut attrs);
+    attrs.control_chars[SpecialCharacterIndices::VMIN as usize] = 0;
+    attrs.control_chars[SpecialCharacterIndices::VTIME as usize] = 1;
+    termios::tcsetattr(&port, SetArg::TCSANOW, &attrs)?;
+    Ok(port)
+}
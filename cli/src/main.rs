@@ -0,0 +1,348 @@
+//! Drives the picoram tester over USB serial: starts tests with the console's commands, follows the
+//! event stream, and saves the results.
+
+mod console;
+mod device;
+mod results;
+mod stream;
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use clap::{Args, Parser, Subcommand};
+use picoram_core::{
+    console::Request,
+    protocol::{Chip, Message},
+};
+
+use console::Console;
+use results::Session;
+use stream::Stream;
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the connected testers
+    List,
+    /// Run a test and save its results
+    Run {
+        /// MovInv, MarchC- or MATS+
+        test: String,
+        /// Timing preset: default, 150ns, 120ns, 100ns or 80ns
+        #[arg(long)]
+        timing: Option<String>,
+        /// Number of passes, the tester stops by itself after them
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        passes: u32,
+        /// Seconds without news from the tester before giving up
+        #[arg(long, default_value_t = 10)]
+        timeout: u64,
+        #[command(flatten)]
+        ports: Ports,
+        #[command(flatten)]
+        outputs: Outputs,
+    },
+    /// Follow the tester's events, e.g. of a run started with its buttons
+    Tail {
+        /// Number of results to wait for, until interrupted otherwise
+        #[arg(long)]
+        count: Option<usize>,
+        #[command(flatten)]
+        ports: Ports,
+        #[command(flatten)]
+        outputs: Outputs,
+    },
+}
+
+/// The tester's ports, found through sysfs if not given
+#[derive(Args)]
+struct Ports {
+    /// Console port, e.g. /dev/ttyACM0
+    #[arg(long, requires = "stream")]
+    console: Option<PathBuf>,
+    /// Event stream port, e.g. /dev/ttyACM1
+    #[arg(long, requires = "console")]
+    stream: Option<PathBuf>,
+}
+
+impl Ports {
+    fn resolve(self) -> Result<(PathBuf, PathBuf)> {
+        match (self.console, self.stream) {
+            (Some(console), Some(stream)) => Ok((console, stream)),
+            _ => device::find_ports(),
+        }
+    }
+}
+
+/// Where to save the results, each file is rewritten after every result
+#[derive(Args)]
+struct Outputs {
+    /// Save all results as JSON
+    #[arg(long)]
+    json: Option<PathBuf>,
+    /// Save all results as CSV
+    #[arg(long)]
+    csv: Option<PathBuf>,
+    /// Save the failure map of the last failed pass as PNG
+    #[arg(long)]
+    map: Option<PathBuf>,
+}
+
+impl Outputs {
+    fn save(&self, session: &Session) -> Result<()> {
+        let results = session.results();
+        if let Some(path) = &self.json {
+            results::write_json(path, results)?;
+        }
+        if let Some(path) = &self.csv {
+            results::write_csv(path, results)?;
+        }
+        let last_failure = results
+            .iter()
+            .rev()
+            .find_map(|result| result.failure.as_ref());
+        if let (Some(path), Some(failure)) = (&self.map, last_failure) {
+            results::write_map(path, failure)?;
+        }
+        Ok(())
+    }
+}
+
+fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::List => list(),
+        Command::Run {
+            test,
+            timing,
+            passes,
+            timeout,
+            ports,
+            outputs,
+        } => {
+            let (console, stream) = ports.resolve()?;
+            run(
+                &console,
+                &stream,
+                &test,
+                timing.as_deref(),
+                passes,
+                Duration::from_secs(timeout),
+                &outputs,
+            )
+        }
+        Command::Tail {
+            count,
+            ports,
+            outputs,
+        } => {
+            let (_, stream) = ports.resolve()?;
+            tail(&stream, count, &outputs)
+        }
+    }
+}
+
+fn list() -> Result<()> {
+    let devices = device::list()?;
+    if devices.is_empty() {
+        eprintln!("no tester found");
+    }
+    for device in devices {
+        let port = |path: Option<PathBuf>| match path {
+            Some(path) => path.display().to_string(),
+            None => "-".to_owned(),
+        };
+        println!(
+            "{}: console {}, stream {}",
+            device.usb_path.display(),
+            port(device.console),
+            port(device.stream)
+        );
+    }
+    Ok(())
+}
+
+fn run(
+    console_path: &Path,
+    stream_path: &Path,
+    test: &str,
+    timing: Option<&str>,
+    passes: u32,
+    timeout: Duration,
+    outputs: &Outputs,
+) -> Result<()> {
+    // the stream first, so that no event after the commands is missed
+    let mut stream = Stream::open(stream_path)?;
+    let mut console = Console::open(console_path)?;
+    let mut session = Session::default();
+
+    // the stream only tells about chip changes
+    let detected = console.command("detect")?;
+    if let Some(chip) = detected
+        .lines()
+        .find_map(|line| line.strip_prefix("chip: "))
+    {
+        if chip != "?" {
+            session.update(&Message::Chip(Chip::Detected(chip)));
+        }
+    }
+    if let Some(timing) = timing {
+        console.command(&format!("set timing {timing}"))?;
+    }
+    console.command(&format!("set loops {passes}"))?;
+    console.command(&format!("run {test}"))?;
+    // the name the tester reports the run with, it accepted the test so it's known
+    let Ok(Request::Run(test)) = Request::parse(&format!("run {test}")) else {
+        bail!("unknown test {test}");
+    };
+    let test = test.name();
+
+    let mut started = false;
+    let mut done = 0;
+    while done < passes {
+        let Some(mut frame) = stream.next_frame(Some(Instant::now() + timeout))? else {
+            bail!("no news from the tester for {} s", timeout.as_secs());
+        };
+        let message = match stream::decode(&mut frame) {
+            Ok(message) => message,
+            Err(err) => {
+                eprintln!("{err}");
+                continue;
+            }
+        };
+        // results of a pass from before the command, or of a run that was still going on and is
+        // only restarted with some of the new settings
+        if let Message::Started {
+            test: started_test,
+            loops,
+            ..
+        } = message
+        {
+            let ours = started_test == test && loops == Some(passes);
+            if started && !ours {
+                bail!("the run was changed on the tester after {done} of {passes} passes");
+            }
+            started = ours;
+        }
+        if !started && !matches!(message, Message::Chip(_)) {
+            continue;
+        }
+
+        print_message(&message);
+        if session.update(&message).is_some() {
+            outputs.save(&session)?;
+            done += 1;
+        } else if matches!(message, Message::Stopped) {
+            bail!("the tester stopped after {done} of {passes} passes");
+        }
+    }
+
+    console.command("stop")?;
+    match session.results().iter().all(|result| result.passed) {
+        true => Ok(()),
+        false => bail!("the chip failed"),
+    }
+}
+
+fn tail(stream_path: &Path, count: Option<usize>, outputs: &Outputs) -> Result<()> {
+    let mut stream = Stream::open(stream_path)?;
+    let mut session = Session::default();
+    while count.is_none_or(|count| session.results().len() < count) {
+        let Some(mut frame) = stream.next_frame(None)? else {
+            continue;
+        };
+        let message = match stream::decode(&mut frame) {
+            Ok(message) => message,
+            Err(err) => {
+                eprintln!("{err}");
+                continue;
+            }
+        };
+        print_message(&message);
+        if session.update(&message).is_some() {
+            outputs.save(&session)?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints `message` for humans: events on stdout, the progress on stderr on a line of its own that
+/// is overwritten.
+fn print_message(message: &Message) {
+    if let Message::Progress {
+        step,
+        steps,
+        row,
+        rows,
+        elapsed_ms,
+    } = *message
+    {
+        eprint!(
+            "\rstep {}/{steps}, row {row}/{rows}, {}.{} s ",
+            step + 1,
+            elapsed_ms / 1000,
+            elapsed_ms % 1000 / 100
+        );
+        let _ = std::io::stderr().flush();
+        return;
+    }
+    // clear the progress line
+    eprint!("\r\x1b[K");
+
+    match *message {
+        Message::Chip(Chip::None) => println!("no chip"),
+        Message::Chip(Chip::Wrong) => println!("wrong chip"),
+        Message::Chip(Chip::Detected(name)) => println!("chip: {name}"),
+        Message::Started {
+            test,
+            preset,
+            clock_mhz,
+            loops,
+        } => {
+            print!("started {test}");
+            if let Some(preset) = preset {
+                print!(", timing {preset}");
+            }
+            print!(", {clock_mhz} MHz");
+            match loops {
+                Some(loops) => println!(", {loops} passes"),
+                None => println!(", until stopped"),
+            }
+        }
+        Message::Stopped => println!("stopped"),
+        Message::Phase {
+            step,
+            steps,
+            failed_bits,
+        } => match failed_bits {
+            0 => println!("  step {}/{steps}: ok", step + 1),
+            _ => println!("  step {}/{steps}: {failed_bits} bits failed", step + 1),
+        },
+        Message::Passed { pass_count } => println!("passed ({pass_count} in a row)"),
+        Message::Failed {
+            failed_bits,
+            last_addr,
+            ..
+        } => println!("FAILED: {failed_bits} bits, last at 0x{last_addr:x}"),
+        Message::Graded { preset, grade } => match (preset, grade) {
+            (Some(preset), Some(grade)) => println!("passes with {preset}, grade {grade}"),
+            (Some(preset), None) => println!("passes with {preset}"),
+            _ => println!("fails with every timing preset"),
+        },
+        Message::AccessTimes(Some(times)) => println!(
+            "access times: tRAC {}±{} ns, tCAC {}±{} ns",
+            times.t_rac_ns, times.uncertainty_ns, times.t_cac_ns, times.uncertainty_ns
+        ),
+        Message::AccessTimes(None) => println!("access times: no valid data"),
+        Message::Progress { .. } => {}
+    }
+}
@@ -0,0 +1,232 @@
+//! Collecting the results of a session from the stream, and saving them.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use picoram_core::protocol::{self, Chip, Message};
+use serde::Serialize;
+
+/// Size of a bin of the failure map in the PNG (pixels)
+const MAP_SCALE: usize = 8;
+
+/// Result of a pass
+#[derive(Serialize, Debug, Clone)]
+pub struct TestResult {
+    /// Number of the pass in the session, from 1
+    pub pass: usize,
+    pub chip: Option<String>,
+    /// The settings, unknown for a run that was started before connecting
+    pub test: Option<String>,
+    pub preset: Option<String>,
+    pub clock_mhz: Option<u16>,
+    pub passed: bool,
+    /// The phases that ran, a pass stops after the first failed one
+    pub phases: Vec<Phase>,
+    pub failure: Option<Failure>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct Phase {
+    pub step: u8,
+    pub failed_bits: u32,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Failure {
+    pub failed_bits: u32,
+    pub last_addr: u32,
+    pub fails_per_chip: Vec<u32>,
+    /// Failure map, one string per bin row with `#` for failed and `.` for passed bins
+    pub map: Vec<String>,
+}
+
+/// Settings of the running test, from [`Message::Started`]
+struct Run {
+    test: String,
+    preset: Option<String>,
+    clock_mhz: u16,
+}
+
+/// The state of the tester, as far as the stream told it
+#[derive(Default)]
+pub struct Session {
+    chip: Option<String>,
+    run: Option<Run>,
+    phases: Vec<Phase>,
+    results: Vec<TestResult>,
+}
+
+impl Session {
+    /// Updates the session with `message`, returns the new result if it completes one.
+    pub fn update(&mut self, message: &Message) -> Option<&TestResult> {
+        match *message {
+            Message::Chip(chip) => {
+                self.chip = match chip {
+                    Chip::Detected(name) => Some(name.to_owned()),
+                    Chip::None | Chip::Wrong => None,
+                };
+            }
+            Message::Started {
+                test,
+                preset,
+                clock_mhz,
+                ..
+            } => {
+                self.run = Some(Run {
+                    test: test.to_owned(),
+                    preset: preset.map(str::to_owned),
+                    clock_mhz,
+                });
+                self.phases.clear();
+            }
+            Message::Stopped => self.run = None,
+            Message::Phase {
+                step, failed_bits, ..
+            } => self.phases.push(Phase { step, failed_bits }),
+            Message::Passed { .. } => return self.finish(None),
+            Message::Failed {
+                failed_bits,
+                last_addr,
+                ref fails_per_chip,
+                map_size,
+                map,
+            } => {
+                let map = (0..usize::from(map_size))
+                    .map(|r| {
+                        (0..usize::from(map_size))
+                            .map(|c| match protocol::map_failed(map, map_size, r, c) {
+                                true => '#',
+                                false => '.',
+                            })
+                            .collect()
+                    })
+                    .collect();
+                return self.finish(Some(Failure {
+                    failed_bits,
+                    last_addr,
+                    fails_per_chip: fails_per_chip.to_vec(),
+                    map,
+                }));
+            }
+            Message::Progress { .. } | Message::Graded { .. } | Message::AccessTimes(_) => {}
+        }
+        None
+    }
+
+    fn finish(&mut self, failure: Option<Failure>) -> Option<&TestResult> {
+        let run = self.run.as_ref();
+        self.results.push(TestResult {
+            pass: self.results.len() + 1,
+            chip: self.chip.clone(),
+            test: run.map(|run| run.test.clone()),
+            preset: run.and_then(|run| run.preset.clone()),
+            clock_mhz: run.map(|run| run.clock_mhz),
+            passed: failure.is_none(),
+            phases: std::mem::take(&mut self.phases),
+            failure,
+        });
+        self.results.last()
+    }
+
+    pub fn results(&self) -> &[TestResult] {
+        &self.results
+    }
+}
+
+/// Writes `results` as a JSON array.
+pub fn write_json(path: &Path, results: &[TestResult]) -> Result<()> {
+    let mut file = create(path)?;
+    serde_json::to_writer_pretty(&mut file, results)?;
+    writeln!(file)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Writes `results` as CSV, one line per pass. The failure maps are left out.
+pub fn write_csv(path: &Path, results: &[TestResult]) -> Result<()> {
+    let mut file = create(path)?;
+    writeln!(
+        file,
+        "pass,chip,test,preset,clock_mhz,passed,failed_step,failed_bits,last_addr,fails_per_chip"
+    )?;
+    for result in results {
+        let failed_step = match result.failure {
+            Some(_) => result.phases.last().map(|phase| phase.step.to_string()),
+            None => None,
+        };
+        let failure = result.failure.as_ref();
+        let fails_per_chip = failure.map(|failure| {
+            let fails: Vec<_> = failure.fails_per_chip.iter().map(u32::to_string).collect();
+            fails.join(" ")
+        });
+        writeln!(
+            file,
+            "{},{},{},{},{},{},{},{},{},{}",
+            result.pass,
+            quote(result.chip.as_deref().unwrap_or("")),
+            quote(result.test.as_deref().unwrap_or("")),
+            quote(result.preset.as_deref().unwrap_or("")),
+            result
+                .clock_mhz
+                .map_or(String::new(), |clock_mhz| clock_mhz.to_string()),
+            result.passed,
+            failed_step.unwrap_or_default(),
+            failure.map_or(0, |failure| failure.failed_bits),
+            failure.map_or(String::new(), |failure| format!(
+                "0x{:x}",
+                failure.last_addr
+            )),
+            quote(&fails_per_chip.unwrap_or_default()),
+        )?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+/// Quotes a CSV field if needed.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Writes the failure map of `failure` as a grayscale PNG, failed bins black on white.
+pub fn write_map(path: &Path, failure: &Failure) -> Result<()> {
+    let size = failure.map.len();
+    if size == 0 {
+        bail!("the failure map is empty");
+    }
+    let side = size * MAP_SCALE;
+    let mut pixels = Vec::with_capacity(side * side);
+    for row in &failure.map {
+        let line: Vec<u8> = row
+            .chars()
+            .flat_map(|bin| {
+                let value = if bin == '#' { 0 } else { 0xff };
+                [value; MAP_SCALE]
+            })
+            .collect();
+        for _ in 0..MAP_SCALE {
+            pixels.extend_from_slice(&line);
+        }
+    }
+
+    let mut encoder = png::Encoder::new(create(path)?, side as u32, side as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()?;
+    Ok(())
+}
+
+fn create(path: &Path) -> Result<BufWriter<File>> {
+    let file = File::create(path).with_context(|| format!("can't create {}", path.display()))?;
+    Ok(BufWriter::new(file))
+}
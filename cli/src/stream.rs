@@ -0,0 +1,76 @@
+//! The tester's event stream port, see [`picoram_core::protocol`].
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{ErrorKind, Read},
+    path::Path,
+    time::Instant,
+};
+
+use anyhow::{bail, Result};
+use picoram_core::protocol::{Message, MAX_FRAME_LEN};
+
+use crate::device;
+
+pub struct Stream {
+    port: File,
+    /// Bytes of the frame being received
+    frame: Vec<u8>,
+    /// Received bytes after the end of the last frame
+    pending: VecDeque<u8>,
+    /// Whether the current frame got too long, it's dropped then
+    skip: bool,
+}
+
+impl Stream {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self {
+            port: device::open(path)?,
+            frame: Vec::new(),
+            pending: VecDeque::new(),
+            skip: false,
+        })
+    }
+
+    /// Waits for the next frame until `deadline`, returns it COBS-encoded with the terminating 0.
+    /// Returns `None` on timeout.
+    ///
+    /// The first frame may be cut off, if the tester was in the middle of it when connecting.
+    pub fn next_frame(&mut self, deadline: Option<Instant>) -> Result<Option<Vec<u8>>> {
+        loop {
+            while let Some(b) = self.pending.pop_front() {
+                if b != 0 {
+                    self.frame.push(b);
+                    self.skip |= self.frame.len() >= MAX_FRAME_LEN;
+                    continue;
+                }
+
+                let mut frame = std::mem::take(&mut self.frame);
+                if std::mem::take(&mut self.skip) || frame.is_empty() {
+                    continue;
+                }
+                frame.push(0);
+                return Ok(Some(frame));
+            }
+
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(None);
+            }
+            let mut buf = [0; 256];
+            match self.port.read(&mut buf) {
+                Ok(n) => self.pending.extend(&buf[..n]),
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+/// Decodes a frame from [`Stream::next_frame`], in place.
+pub fn decode(frame: &mut [u8]) -> Result<Message<'_>> {
+    match postcard::from_bytes_cobs(frame) {
+        Ok(message) => Ok(message),
+        Err(err) => bail!("invalid message from the tester: {err}"),
+    }
+}
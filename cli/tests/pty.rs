@@ -0,0 +1,521 @@
+//! Runs `picoram-cli` against a simulated tester, whose console and stream ports are ptys.
+
+use std::{
+    fs::{self, File},
+    io::{Read, Write},
+    os::fd::OwnedFd,
+    path::PathBuf,
+    process::{Command, Output},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use nix::{
+    pty::openpty,
+    sys::termios::{self, SetArg},
+    unistd::ttyname,
+};
+use picoram_core::{
+    console::{Request, HELP},
+    march::Test,
+    memory::{FailureMap, MemoryUnderTest},
+    protocol::{Chip, Message, CHIP_POSITIONS, MAX_FRAME_LEN},
+    timings::TimingPreset,
+};
+use picoram_sim::{Fault, SimDram};
+
+/// A serial port of the simulated tester
+struct Port {
+    /// The tester's end
+    master: File,
+    /// The host's end, kept open so that the master doesn't see a hangup between two opens
+    _slave: OwnedFd,
+    path: PathBuf,
+}
+
+fn port() -> Port {
+    let pty = openpty(None, None).unwrap();
+    let mut attrs = termios::tcgetattr(&pty.slave).unwrap();
+    termios::cfmakeraw(&mut attrs);
+    termios::tcsetattr(&pty.slave, SetArg::TCSANOW, &attrs).unwrap();
+    Port {
+        path: ttyname(&pty.slave).unwrap(),
+        master: pty.master.into(),
+        _slave: pty.slave,
+    }
+}
+
+fn send(stream: &mut File, message: &Message) {
+    let mut buf = [0; MAX_FRAME_LEN];
+    stream
+        .write_all(postcard::to_slice_cobs(message, &mut buf).unwrap())
+        .unwrap();
+}
+
+/// The tester's state, as far as the simulation needs it
+struct Tester {
+    mem: SimDram,
+    console: File,
+    stream: File,
+    /// Number of passes per run, `None` until stopped
+    loops: Option<u32>,
+    /// The test of the run and its passes so far, `None` while stopped
+    running: Option<(Test, u32)>,
+    /// Passes in a row
+    pass_count: u32,
+}
+
+/// How long a pass takes on the simulated tester, so that it doesn't fill the stream while the host
+/// is still busy with the console
+const PASS_TIME: Duration = Duration::from_millis(10);
+
+/// Plays a tester with `mem` inserted: answers the console with the firmware's parser, and runs
+/// the tests on `mem`, streaming their results like the firmware. Returns the received commands.
+///
+/// If `running` is given, the tester is already running that test until stopped.
+fn simulate(mem: SimDram, running: Option<Test>, console: File, stream: File) -> Receiver<String> {
+    let (commands, received) = mpsc::channel();
+    let (lines_tx, lines) = mpsc::channel();
+
+    // the console's line editing, echoing the input
+    let mut input = console.try_clone().unwrap();
+    thread::spawn(move || {
+        let mut line = String::new();
+        let mut buf = [0; 64];
+        while let Ok(n) = input.read(&mut buf) {
+            for &b in &buf[..n] {
+                if b != b'\r' {
+                    line.push(char::from(b));
+                    input.write_all(&[b]).unwrap();
+                    continue;
+                }
+                input.write_all(b"\r\n").unwrap();
+                if lines_tx.send(std::mem::take(&mut line)).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    thread::spawn(move || {
+        let mut tester = Tester {
+            mem,
+            console,
+            stream,
+            loops: None,
+            running: running.map(|test| (test, 0)),
+            pass_count: 7,
+        };
+        // a result of a run from before connecting
+        send(&mut tester.stream, &Message::Passed { pass_count: 7 });
+
+        loop {
+            // commands are handled between two passes
+            let line = match tester.running {
+                Some(_) => match lines.recv_timeout(PASS_TIME) {
+                    Ok(line) => Some(line),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                },
+                None => match lines.recv() {
+                    Ok(line) => Some(line),
+                    Err(_) => return,
+                },
+            };
+            if let Some(line) = line {
+                if commands.send(line.clone()).is_err() {
+                    return;
+                }
+                tester.answer(&line);
+            }
+            if tester.running.is_some() {
+                tester.pass();
+            }
+        }
+    });
+    received
+}
+
+impl Tester {
+    fn answer(&mut self, line: &str) {
+        if line.trim().is_empty() {
+            write!(self.console, "> ").unwrap();
+            return;
+        }
+        let answer = match Request::parse(line) {
+            Err(message) => format!("error: {message}\r\n"),
+            Ok(Request::Detect) => format!("chip: {}\r\n", self.mem.detect().unwrap()),
+            Ok(Request::Run(test)) => {
+                self.start(test);
+                "ok\r\n".to_owned()
+            }
+            Ok(Request::Stop) => {
+                if self.running.take().is_some() {
+                    send(&mut self.stream, &Message::Stopped);
+                }
+                "ok\r\n".to_owned()
+            }
+            Ok(Request::SetTiming(preset)) => {
+                self.mem.set_timing_preset(preset);
+                self.restart();
+                "ok\r\n".to_owned()
+            }
+            Ok(Request::SetLoops(loops)) => {
+                self.loops = loops;
+                self.restart();
+                "ok\r\n".to_owned()
+            }
+            Ok(Request::Help) => HELP.replace('\n', "\r\n"),
+            Ok(Request::Status | Request::Dump) => "error: not simulated\r\n".to_owned(),
+        };
+        write!(self.console, "{answer}> ").unwrap();
+    }
+
+    fn start(&mut self, test: Test) {
+        self.running = Some((test, 0));
+        send(
+            &mut self.stream,
+            &Message::Chip(Chip::Detected(self.mem.detect().unwrap())),
+        );
+        self.send_started(test);
+        // a frame cut off, and garbage
+        self.stream
+            .write_all(&[0x05, 0x01, 0x00, 0xff, 0xff, 0x00])
+            .unwrap();
+    }
+
+    /// Starts a running test over with the changed settings, which the firmware reports like a new
+    /// run.
+    fn restart(&mut self) {
+        if let Some((test, passes)) = &mut self.running {
+            *passes = 0;
+            let test = *test;
+            self.send_started(test);
+        }
+    }
+
+    fn send_started(&mut self, test: Test) {
+        send(
+            &mut self.stream,
+            &Message::Started {
+                test: test.name(),
+                preset: self.mem.timing_preset().map(TimingPreset::name),
+                clock_mhz: 125,
+                loops: self.loops,
+            },
+        );
+    }
+
+    /// Runs a pass of the running test, and stops the run after the selected number of passes.
+    fn pass(&mut self) {
+        let Some((test, passes)) = &mut self.running else {
+            return;
+        };
+        let test = *test;
+        *passes += 1;
+        let done = Some(*passes) == self.loops;
+
+        let steps = test.elements().len() as u8;
+        let phase = |stream: &mut File, step, failed_bits| {
+            send(
+                stream,
+                &Message::Phase {
+                    step,
+                    steps,
+                    failed_bits,
+                },
+            );
+        };
+        match self.mem.run_test(test.elements()) {
+            Ok(()) => {
+                self.pass_count += 1;
+                for step in 0..steps {
+                    phase(&mut self.stream, step, 0);
+                }
+                send(
+                    &mut self.stream,
+                    &Message::Passed {
+                        pass_count: self.pass_count,
+                    },
+                );
+            }
+            Err(err) => {
+                self.pass_count = 0;
+                let failed_bits = err.num_failed_bits as u32;
+                for step in 0..=err.step as u8 {
+                    let failed = if step == err.step as u8 {
+                        failed_bits
+                    } else {
+                        0
+                    };
+                    phase(&mut self.stream, step, failed);
+                }
+                let mut fails_per_chip = [0; CHIP_POSITIONS];
+                for (bit, &fails) in err.fails_per_bit.iter().enumerate() {
+                    if let Some(position) = self.mem.chip_position(bit as u8) {
+                        fails_per_chip[usize::from(position)] += fails as u32;
+                    }
+                }
+                send(
+                    &mut self.stream,
+                    &Message::Failed {
+                        failed_bits,
+                        last_addr: (err.row * self.mem.cols() + err.col) as u32,
+                        fails_per_chip,
+                        map_size: FailureMap::SIZE as u8,
+                        map: err.map.as_bytes(),
+                    },
+                );
+            }
+        }
+
+        if done {
+            self.running = None;
+            send(&mut self.stream, &Message::Stopped);
+        }
+    }
+}
+
+/// The step of MovInv that first expects a 0 from the stuck bit, the background of column 17 is
+/// all 1s
+const FAILED_STEP: usize = 2;
+
+/// A 256K × 9 SIMM with data bit 1 of a cell stuck at 1, i.e. chip U2 fails
+fn failing_simm() -> SimDram {
+    SimDram::simm256k9().with_fault(Fault::StuckAt {
+        cell: (300, 17),
+        bit: 1,
+        value: true,
+    })
+}
+
+fn cli(args: &[&str], console: &Port, stream: &Port) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_picoram-cli"))
+        .args(args)
+        .arg("--console")
+        .arg(&console.path)
+        .arg("--stream")
+        .arg(&stream.path)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn run_saves_results() {
+    let console = port();
+    let stream = port();
+    let commands = simulate(
+        failing_simm(),
+        None,
+        console.master.try_clone().unwrap(),
+        stream.master.try_clone().unwrap(),
+    );
+    let dir = tempfile::tempdir().unwrap();
+    let json = dir.path().join("results.json");
+    let csv = dir.path().join("results.csv");
+    let map = dir.path().join("map.png");
+
+    let output = cli(
+        &[
+            "run",
+            "movinv",
+            "--timing",
+            "100ns",
+            "--json",
+            json.to_str().unwrap(),
+            "--csv",
+            csv.to_str().unwrap(),
+            "--map",
+            map.to_str().unwrap(),
+        ],
+        &console,
+        &stream,
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.status.success(), "a failed chip is an error");
+    assert!(stdout.contains("started MovInv, timing 100ns, 125 MHz, 1 passes"));
+    // row 300, column 17 of 512
+    assert!(
+        stdout.contains("FAILED: 1 bits, last at 0x25811"),
+        "{stdout}"
+    );
+    assert!(!stdout.contains("passed"), "results from before the run");
+
+    let commands: Vec<_> = commands.try_iter().collect();
+    assert_eq!(
+        commands,
+        [
+            "",
+            "detect",
+            "set timing 100ns",
+            "set loops 1",
+            "run movinv",
+            "stop"
+        ]
+    );
+
+    let results: serde_json::Value = serde_json::from_slice(&fs::read(&json).unwrap()).unwrap();
+    let results = results.as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    let result = &results[0];
+    assert_eq!(result["chip"], "256K SIMM");
+    assert_eq!(result["test"], "MovInv");
+    assert_eq!(result["preset"], "100ns");
+    assert_eq!(result["passed"], false);
+    assert_eq!(result["phases"].as_array().unwrap().len(), FAILED_STEP + 1);
+    assert_eq!(result["failure"]["failed_bits"], 1);
+    assert_eq!(result["failure"]["fails_per_chip"][2], 1);
+    // 512 rows and columns into 46 bins
+    let (bin_row, bin_col) = (300 * 46 / 512, 17 * 46 / 512);
+    let map_rows = result["failure"]["map"].as_array().unwrap();
+    assert_eq!(map_rows.len(), FailureMap::SIZE);
+    for (r, row) in map_rows.iter().enumerate() {
+        let failed: Vec<_> = row.as_str().unwrap().match_indices('#').collect();
+        match r == bin_row {
+            true => assert_eq!(failed, [(bin_col, "#")]),
+            false => assert!(failed.is_empty()),
+        }
+    }
+
+    let csv = fs::read_to_string(&csv).unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(
+        lines[1],
+        format!("1,256K SIMM,MovInv,100ns,125,false,{FAILED_STEP},1,0x25811,0 0 1 0 0 0 0 0 0 0")
+    );
+
+    let decoder = png::Decoder::new(File::open(&map).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    let side = FailureMap::SIZE * 8;
+    assert_eq!((info.width, info.height), (side as u32, side as u32));
+    let pixel = |x: usize, y: usize| pixels[y * side + x];
+    assert_eq!(pixel(bin_col * 8, bin_row * 8), 0);
+    assert_eq!(pixel(bin_col * 8 + 7, bin_row * 8 + 7), 0);
+    assert_eq!(pixel(0, 0), 0xff);
+    assert_eq!(pixel(bin_col * 8 + 8, bin_row * 8), 0xff);
+}
+
+#[test]
+fn run_several_passes() {
+    let console = port();
+    let stream = port();
+    let commands = simulate(
+        SimDram::dram4164(),
+        None,
+        console.master.try_clone().unwrap(),
+        stream.master.try_clone().unwrap(),
+    );
+    let dir = tempfile::tempdir().unwrap();
+    let json = dir.path().join("results.json");
+
+    let output = cli(
+        &[
+            "run",
+            "marchc-",
+            "--passes",
+            "3",
+            "--json",
+            json.to_str().unwrap(),
+        ],
+        &console,
+        &stream,
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    assert!(
+        stdout.contains("started March C-, timing default, 125 MHz, 3 passes"),
+        "{stdout}"
+    );
+    // counted on from the run before connecting
+    assert!(stdout.contains("passed (10 in a row)"), "{stdout}");
+    assert!(commands.try_iter().any(|command| command == "set loops 3"));
+
+    let results: serde_json::Value = serde_json::from_slice(&fs::read(&json).unwrap()).unwrap();
+    let results = results.as_array().unwrap();
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(|result| result["passed"] == true));
+}
+
+#[test]
+fn run_while_running() {
+    let console = port();
+    let stream = port();
+    let _commands = simulate(
+        SimDram::dram4164(),
+        Some(Test::MatsPlus),
+        console.master.try_clone().unwrap(),
+        stream.master.try_clone().unwrap(),
+    );
+    let dir = tempfile::tempdir().unwrap();
+    let json = dir.path().join("results.json");
+
+    let output = cli(
+        &[
+            "run",
+            "marchc-",
+            "--passes",
+            "2",
+            "--json",
+            json.to_str().unwrap(),
+        ],
+        &console,
+        &stream,
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    // the run of MATS+ is restarted by `set loops`, its passes don't count
+    assert!(!stdout.contains("MATS+"), "{stdout}");
+
+    let results: serde_json::Value = serde_json::from_slice(&fs::read(&json).unwrap()).unwrap();
+    let results = results.as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().all(|result| result["test"] == "March C-"));
+}
+
+#[test]
+fn run_unknown_test() {
+    let console = port();
+    let stream = port();
+    let _commands = simulate(
+        SimDram::dram4164(),
+        None,
+        console.master.try_clone().unwrap(),
+        stream.master.try_clone().unwrap(),
+    );
+
+    let output = cli(&["run", "bogus"], &console, &stream);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("unknown test"), "{stderr}");
+}
+
+#[test]
+fn tail_run_in_progress() {
+    let console = port();
+    let stream = port();
+    let _commands = simulate(
+        SimDram::dram4164(),
+        None,
+        console.master.try_clone().unwrap(),
+        stream.master.try_clone().unwrap(),
+    );
+    let dir = tempfile::tempdir().unwrap();
+    let json = dir.path().join("results.json");
+
+    let output = cli(
+        &["tail", "--count", "1", "--json", json.to_str().unwrap()],
+        &console,
+        &stream,
+    );
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("passed (7 in a row)"));
+
+    let results: serde_json::Value = serde_json::from_slice(&fs::read(&json).unwrap()).unwrap();
+    let result = &results[0];
+    assert_eq!(result["passed"], true);
+    assert_eq!(result["test"], serde_json::Value::Null);
+}
@@ -0,0 +1,396 @@
+//! Known DRAM parts, with their organisation, refresh requirements and speed grades.
+//!
+//! A part can be selected with the firmware's `PART`, in which case its timings are used instead of the
+//! default preset, and inserted chips of a different size are rejected. The expected part can also
+//! be changed at runtime (see [`ChipType`]), but then only its size is checked.
+
+use core::ptr;
+
+use crate::{
+    memory::DetectError,
+    timings::{NsTimings, NS_100, NS_120, NS_150, NS_80},
+};
+
+pub struct SpeedGrade {
+    /// Suffix of the part number, e.g. "-15"
+    pub suffix: &'static str,
+    pub timings: NsTimings,
+}
+
+pub struct Part {
+    pub name: &'static str,
+    /// Number of (multiplexed) address lines, 8 for 64K×1, 9 for 256K×1
+    pub num_addr_lines: u8,
+    /// Number of rows that have to be refreshed within `refresh_ms`
+    pub refresh_cycles: u16,
+    pub refresh_ms: u8,
+    pub grades: &'static [SpeedGrade],
+}
+
+impl Part {
+    /// Longest time from one refresh cycle to the next one, when the rows are refreshed in turn (ns)
+    pub const fn refresh_interval(&self) -> u32 {
+        self.refresh_ms as u32 * 1_000_000 / self.refresh_cycles as u32
+    }
+}
+
+/// A part and one of its speed grades.
+#[derive(Clone, Copy)]
+pub struct Selection {
+    pub part: &'static Part,
+    pub grade: &'static SpeedGrade,
+}
+
+/// Selects the speed grade of `part` with the given suffix. Fails to compile when used in a const
+/// and the part doesn't come in that speed grade.
+pub const fn select(part: &'static Part, suffix: &str) -> Selection {
+    let mut i = 0;
+    while i < part.grades.len() {
+        if str_eq(part.grades[i].suffix, suffix) {
+            return Selection {
+                part,
+                grade: &part.grades[i],
+            };
+        }
+        i += 1;
+    }
+
+    panic!("unknown speed grade");
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+// Timings from the datasheets, the access times tRAC and tCAC are the speed grade and tCAS
+// The parts are statics, so that each has a single address for `ChipType::next` to find
+
+pub static TMS4164: Part = Part {
+    name: "TMS4164",
+    num_addr_lines: 8,
+    refresh_cycles: 256,
+    refresh_ms: 4,
+    grades: &[
+        SpeedGrade {
+            suffix: "-12",
+            timings: NsTimings {
+                t_ras: 120,
+                t_cas: 75,
+                t_rcd: 25,
+                t_rp: 80,
+                t_cp: 50,
+                t_rac: 120,
+                t_cac: 75,
+                t_ras_max: 10_000,
+            },
+        },
+        SpeedGrade {
+            suffix: "-15",
+            timings: NsTimings {
+                t_ras: 150,
+                t_cas: 100,
+                t_rcd: 30,
+                t_rp: 100,
+                t_cp: 60,
+                t_rac: 150,
+                t_cac: 100,
+                t_ras_max: 10_000,
+            },
+        },
+        SpeedGrade {
+            suffix: "-20",
+            timings: NsTimings {
+                t_ras: 200,
+                t_cas: 135,
+                t_rcd: 35,
+                t_rp: 120,
+                t_cp: 80,
+                t_rac: 200,
+                t_cac: 135,
+                t_ras_max: 10_000,
+            },
+        },
+    ],
+};
+
+pub static MK4564: Part = Part {
+    name: "MK4564",
+    num_addr_lines: 8,
+    refresh_cycles: 128,
+    refresh_ms: 2,
+    grades: &[
+        SpeedGrade {
+            suffix: "-15",
+            timings: NsTimings {
+                t_ras: 150,
+                t_cas: 100,
+                t_rcd: 20,
+                t_rp: 100,
+                t_cp: 60,
+                t_rac: 150,
+                t_cac: 100,
+                t_ras_max: 10_000,
+            },
+        },
+        SpeedGrade {
+            suffix: "-20",
+            timings: NsTimings {
+                t_ras: 200,
+                t_cas: 135,
+                t_rcd: 25,
+                t_rp: 120,
+                t_cp: 80,
+                t_rac: 200,
+                t_cac: 135,
+                t_ras_max: 10_000,
+            },
+        },
+    ],
+};
+
+pub static HM4864: Part = Part {
+    name: "HM4864",
+    num_addr_lines: 8,
+    refresh_cycles: 128,
+    refresh_ms: 2,
+    grades: &[
+        // Hitachi uses -2 for 150 ns and -3 for 200 ns
+        SpeedGrade {
+            suffix: "-2",
+            timings: NsTimings {
+                t_ras: 150,
+                t_cas: 100,
+                t_rcd: 25,
+                t_rp: 100,
+                t_cp: 60,
+                t_rac: 150,
+                t_cac: 100,
+                t_ras_max: 10_000,
+            },
+        },
+        SpeedGrade {
+            suffix: "-3",
+            timings: NsTimings {
+                t_ras: 200,
+                t_cas: 135,
+                t_rcd: 30,
+                t_rp: 120,
+                t_cp: 80,
+                t_rac: 200,
+                t_cac: 135,
+                t_ras_max: 10_000,
+            },
+        },
+    ],
+};
+
+pub static UPD41256: Part = Part {
+    name: "uPD41256",
+    num_addr_lines: 9,
+    refresh_cycles: 256,
+    refresh_ms: 4,
+    grades: &[
+        SpeedGrade {
+            suffix: "-10",
+            timings: NsTimings {
+                t_ras: 100,
+                t_cas: 50,
+                t_rcd: 25,
+                t_rp: 90,
+                t_cp: 40,
+                t_rac: 100,
+                t_cac: 50,
+                t_ras_max: 10_000,
+            },
+        },
+        SpeedGrade {
+            suffix: "-12",
+            timings: NsTimings {
+                t_ras: 120,
+                t_cas: 60,
+                t_rcd: 25,
+                t_rp: 90,
+                t_cp: 50,
+                t_rac: 120,
+                t_cac: 60,
+                t_ras_max: 10_000,
+            },
+        },
+        SpeedGrade {
+            suffix: "-15",
+            timings: NsTimings {
+                t_ras: 150,
+                t_cas: 75,
+                t_rcd: 25,
+                t_rp: 100,
+                t_cp: 60,
+                t_rac: 150,
+                t_cac: 75,
+                t_ras_max: 10_000,
+            },
+        },
+    ],
+};
+
+pub static KM41256: Part = Part {
+    name: "KM41256",
+    num_addr_lines: 9,
+    refresh_cycles: 256,
+    refresh_ms: 4,
+    grades: &[
+        SpeedGrade {
+            suffix: "-10",
+            timings: NsTimings {
+                t_ras: 100,
+                t_cas: 50,
+                t_rcd: 25,
+                t_rp: 90,
+                t_cp: 45,
+                t_rac: 100,
+                t_cac: 50,
+                t_ras_max: 10_000,
+            },
+        },
+        SpeedGrade {
+            suffix: "-12",
+            timings: NsTimings {
+                t_ras: 120,
+                t_cas: 60,
+                t_rcd: 25,
+                t_rp: 90,
+                t_cp: 50,
+                t_rac: 120,
+                t_cac: 60,
+                t_ras_max: 10_000,
+            },
+        },
+        SpeedGrade {
+            suffix: "-15",
+            timings: NsTimings {
+                t_ras: 150,
+                t_cas: 75,
+                t_rcd: 25,
+                t_rp: 100,
+                t_cp: 60,
+                t_rac: 150,
+                t_cac: 75,
+                t_ras_max: 10_000,
+            },
+        },
+    ],
+};
+
+// the timing presets are from the TMS4256 datasheet
+pub static TMS4256: Part = Part {
+    name: "TMS4256",
+    num_addr_lines: 9,
+    refresh_cycles: 256,
+    refresh_ms: 4,
+    grades: &[
+        SpeedGrade {
+            suffix: "-8",
+            timings: NS_80,
+        },
+        SpeedGrade {
+            suffix: "-10",
+            timings: NS_100,
+        },
+        SpeedGrade {
+            suffix: "-12",
+            timings: NS_120,
+        },
+        SpeedGrade {
+            suffix: "-15",
+            timings: NS_150,
+        },
+    ],
+};
+
+pub static PARTS: &[&Part] = &[&TMS4164, &MK4564, &HM4864, &UPD41256, &KM41256, &TMS4256];
+
+/// Shortest [`Part::refresh_interval`] of all [`PARTS`], for when the part isn't known
+pub const MIN_REFRESH_INTERVAL: u32 = {
+    let mut min = u32::MAX;
+    let mut i = 0;
+    while i < PARTS.len() {
+        let interval = PARTS[i].refresh_interval();
+        if interval < min {
+            min = interval;
+        }
+        i += 1;
+    }
+    min
+};
+
+/// The chip expected in the socket, selectable at runtime from the menu.
+///
+/// Only the size of the inserted chip is checked against it. The timings still come from the
+/// firmware's `PART` or the timing preset.
+#[derive(Clone, Copy)]
+pub enum ChipType {
+    /// Any supported size, named after the detected size
+    Auto,
+    Part(&'static Part),
+}
+
+impl ChipType {
+    /// The chip type of the selected `part`, if any
+    pub const fn selected(part: Option<Selection>) -> Self {
+        match part {
+            Some(selection) => ChipType::Part(selection.part),
+            None => ChipType::Auto,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ChipType::Auto => "auto",
+            ChipType::Part(part) => part.name,
+        }
+    }
+
+    /// Cycles through [`PARTS`], then back to [`ChipType::Auto`].
+    pub fn next(self) -> Self {
+        let next = match self {
+            ChipType::Auto => 0,
+            ChipType::Part(part) => {
+                PARTS
+                    .iter()
+                    .position(|&p| ptr::eq(p, part))
+                    .unwrap_or(PARTS.len())
+                    + 1
+            }
+        };
+        match PARTS.get(next) {
+            Some(&part) => ChipType::Part(part),
+            None => ChipType::Auto,
+        }
+    }
+
+    /// Checks an inserted chip of `num_addr_lines` address lines, returns the name to show for it.
+    pub fn check(
+        self,
+        num_addr_lines: u8,
+        detected: &'static str,
+    ) -> Result<&'static str, DetectError> {
+        match self {
+            ChipType::Auto => Ok(detected),
+            ChipType::Part(part) if part.num_addr_lines != num_addr_lines => {
+                Err(DetectError::WrongChip)
+            }
+            ChipType::Part(part) => Ok(part.name),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! The commands of the tester's serial console, one per line, see [`Request`].
+//!
+//! Parsed here rather than in the firmware, so that the host tools' tests can play a tester that
+//! understands exactly the same commands.
+
+use crate::{march::Test, timings::TimingPreset};
+
+pub const HELP: &str = "commands:
+  detect               show the detected chip
+  run <test>           start a run of MovInv, MarchC- or MATS+
+  stop                 stop the run
+  set timing <preset>  default, 150ns, 120ns, 100ns or 80ns
+  set loops <n>        number of passes per run, or `forever`
+  status               show the settings and the last result
+  dump                 show the last failure with its map
+";
+
+/// A command line
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Request {
+    Detect,
+    Run(Test),
+    Stop,
+    SetTiming(TimingPreset),
+    /// Number of passes per run, `None` until stopped
+    SetLoops(Option<u32>),
+    Status,
+    Dump,
+    Help,
+}
+
+impl Request {
+    /// Parses `line`, returns what's wrong with it otherwise.
+    pub fn parse(line: &str) -> Result<Self, &'static str> {
+        let mut words = line.split_ascii_whitespace();
+        let request = match words.next() {
+            Some("detect") => Request::Detect,
+            Some("run") => {
+                let name = words.next().ok_or("usage: run <test>")?;
+                let test = Test::ALL
+                    .into_iter()
+                    .find(|test| name_matches(name, test.name()))
+                    .ok_or("unknown test")?;
+                Request::Run(test)
+            }
+            Some("stop") => Request::Stop,
+            Some("set") => match words.next() {
+                Some("timing") => {
+                    let name = words.next().ok_or("usage: set timing <preset>")?;
+                    let preset = core::iter::once(TimingPreset::Default)
+                        .chain(TimingPreset::BY_SPEED)
+                        .find(|preset| name_matches(name, preset.name()))
+                        .ok_or("unknown timing preset")?;
+                    Request::SetTiming(preset)
+                }
+                Some("loops") => {
+                    let loops = match words.next().ok_or("usage: set loops <n>")? {
+                        "forever" => None,
+                        n => match n.parse() {
+                            Ok(0) | Err(_) => return Err("loops must be a positive number"),
+                            Ok(n) => Some(n),
+                        },
+                    };
+                    Request::SetLoops(loops)
+                }
+                _ => return Err("usage: set timing <preset> or set loops <n>"),
+            },
+            Some("status") => Request::Status,
+            Some("dump") => Request::Dump,
+            Some("help") => Request::Help,
+            _ => return Err("unknown command, try `help`"),
+        };
+        match words.next() {
+            Some(_) => Err("too many arguments"),
+            None => Ok(request),
+        }
+    }
+}
+
+/// Whether `arg` is `name`, ignoring case and spaces (e.g. `marchc-` for "March C-").
+fn name_matches(arg: &str, name: &str) -> bool {
+    let mut name = name.bytes().filter(|&b| b != b' ');
+    arg.bytes()
+        .all(|b| name.next().is_some_and(|n| n.eq_ignore_ascii_case(&b)))
+        && name.next().is_none()
+}
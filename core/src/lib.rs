@@ -0,0 +1,15 @@
+//! The hardware-independent part of picoram: the test algorithms, the memory interface they run
+//! on, the timing and result types, and the protocol to the host tools.
+//!
+//! Shared by the firmware and the host tools, which test it against simulated memories.
+
+#![no_std]
+
+pub mod chipdb;
+pub mod console;
+pub mod march;
+pub mod memory;
+pub mod progress;
+pub mod protocol;
+pub mod speedgrade;
+pub mod timings;
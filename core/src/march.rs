@@ -0,0 +1,251 @@
+//! March-style test algorithms, shared by all memories under test.
+//!
+//! A march test is a list of [`Element`]s. Each element walks over the whole memory in the given
+//! [`Order`], applying all of its operations to one word before moving on to the next.
+
+use crate::{
+    memory::{FailureMap, MemoryUnderTest, TestError, MAX_DATA_BITS},
+    progress,
+};
+
+use Op::{Read, Write};
+use Order::{Down, Up};
+
+/// Data background of the test patterns: bit `n` decides whether the words in column `n` (mod 32)
+/// start out as all ones or all zeros.
+const PATTERN: u32 = u32::MAX;
+
+#[derive(Clone, Copy)]
+pub enum Order {
+    Up,
+    Down,
+}
+
+impl Order {
+    /// Index of the `i`th visited out of `len` rows/columns
+    #[inline(always)]
+    pub fn index(self, i: usize, len: usize) -> usize {
+        match self {
+            Up => i,
+            Down => len - 1 - i,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Op {
+    /// Write the pattern (inverted if `true`)
+    Write(bool),
+    /// Read and verify the pattern (inverted if `true`)
+    Read(bool),
+}
+
+pub struct Element {
+    pub order: Order,
+    pub ops: &'static [Op],
+}
+
+/// Moving inversions (as explained over
+/// [here](https://www.memtest86.com/tech_memtest-algoritm.html))
+pub const MOVING_INVERSIONS: &[Element] = &[
+    Element {
+        order: Up,
+        ops: &[Write(false)],
+    },
+    Element {
+        order: Up,
+        ops: &[Read(false), Write(true)],
+    },
+    Element {
+        order: Up,
+        ops: &[Read(true)],
+    },
+    Element {
+        order: Down,
+        ops: &[Write(false)],
+    },
+    Element {
+        order: Down,
+        ops: &[Read(false), Write(true)],
+    },
+    Element {
+        order: Down,
+        ops: &[Read(true)],
+    },
+];
+
+/// March C-, detects stuck-at, transition and most coupling faults
+pub const MARCH_C_MINUS: &[Element] = &[
+    Element {
+        order: Up,
+        ops: &[Write(false)],
+    },
+    Element {
+        order: Up,
+        ops: &[Read(false), Write(true)],
+    },
+    Element {
+        order: Up,
+        ops: &[Read(true), Write(false)],
+    },
+    Element {
+        order: Down,
+        ops: &[Read(false), Write(true)],
+    },
+    Element {
+        order: Down,
+        ops: &[Read(true), Write(false)],
+    },
+    Element {
+        order: Up,
+        ops: &[Read(false)],
+    },
+];
+
+/// MATS+, a short test for stuck-at and address decoder faults
+pub const MATS_PLUS: &[Element] = &[
+    Element {
+        order: Up,
+        ops: &[Write(false)],
+    },
+    Element {
+        order: Up,
+        ops: &[Read(false), Write(true)],
+    },
+    Element {
+        order: Down,
+        ops: &[Read(true), Write(false)],
+    },
+];
+
+/// The test algorithms selectable from the menu
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Test {
+    MovingInversions,
+    MarchCMinus,
+    MatsPlus,
+}
+
+impl Test {
+    pub const ALL: [Test; 3] = [Test::MovingInversions, Test::MarchCMinus, Test::MatsPlus];
+
+    pub fn elements(self) -> &'static [Element] {
+        match self {
+            Test::MovingInversions => MOVING_INVERSIONS,
+            Test::MarchCMinus => MARCH_C_MINUS,
+            Test::MatsPlus => MATS_PLUS,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Test::MovingInversions => "MovInv",
+            Test::MarchCMinus => "March C-",
+            Test::MatsPlus => "MATS+",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Test::MovingInversions => Test::MarchCMinus,
+            Test::MarchCMinus => Test::MatsPlus,
+            Test::MatsPlus => Test::MovingInversions,
+        }
+    }
+}
+
+/// The (non-inverted) data written to column `col`, for a memory with data bits `mask`.
+#[inline(always)]
+pub fn background(col: usize, mask: u32) -> u32 {
+    if PATTERN.rotate_right(col as u32) & 1 != 0 {
+        mask
+    } else {
+        0
+    }
+}
+
+/// Runs the march test `test` on `mem`.
+///
+/// Stops after the first element that found any failed bits. Runs from RAM on the RP2040, like the
+/// cycle functions of the drivers.
+#[cfg_attr(target_os = "none", link_section = ".ram_text")]
+#[allow(clippy::result_large_err)]
+pub fn run<M: MemoryUnderTest>(mem: &mut M, test: &[Element]) -> Result<(), TestError> {
+    let rows = mem.rows();
+    let cols = mem.cols();
+    let mask = u32::MAX >> (32 - u32::from(mem.data_bits()));
+
+    let mut num_failed_bits = 0;
+    let mut failed_step = 0;
+    let mut last_failed_bit = None;
+    let mut fails_per_bit = [0; MAX_DATA_BITS];
+    let mut map = FailureMap::new(rows, cols);
+
+    progress::start(test.len(), rows);
+    for (step, element) in test.iter().enumerate() {
+        progress::step(step);
+        for i in 0..rows {
+            let row = element.order.index(i, rows);
+            mem.open_row(row);
+
+            for j in 0..cols {
+                let col = element.order.index(j, cols);
+                let background = background(col, mask);
+                let inverse = !background & mask;
+
+                for &op in element.ops {
+                    match op {
+                        Write(inverted) => {
+                            mem.write(col, if inverted { inverse } else { background });
+                        }
+                        Read(inverted) => {
+                            let expected = if inverted { inverse } else { background };
+                            let read = mem.read(col) & mask;
+                            if read != expected {
+                                // the bookkeeping would count against tRAS(max), which drivers
+                                // only budget the accesses for
+                                mem.close_row();
+                                let mut failed = read ^ expected;
+                                num_failed_bits += failed.count_ones() as usize;
+                                last_failed_bit = Some((row, col));
+                                map.mark(row, col);
+                                while failed != 0 {
+                                    fails_per_bit[failed.trailing_zeros() as usize] += 1;
+                                    // clear lowest set bit
+                                    failed &= failed - 1;
+                                }
+                                mem.open_row(row);
+                                // leave the failed word alone
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+
+            mem.close_row();
+            progress::rows_done(i + 1);
+        }
+
+        if num_failed_bits > 0 {
+            failed_step = step;
+            break;
+        }
+    }
+
+    progress::finish();
+
+    if num_failed_bits == 0 {
+        Ok(())
+    } else {
+        let (row, col) = last_failed_bit.unwrap();
+        Err(TestError {
+            num_failed_bits,
+            step: failed_step,
+            row,
+            col,
+            fails_per_bit,
+            map,
+        })
+    }
+}
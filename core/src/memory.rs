@@ -0,0 +1,150 @@
+//! Common interface for the memory chips under test.
+
+use crate::{
+    chipdb::ChipType,
+    march::{self, Element},
+    timings::TimingPreset,
+};
+
+/// Maximum number of data bits of any memory under test (SIMMs with parity)
+pub const MAX_DATA_BITS: usize = 9;
+
+/// A memory chip that the test algorithms in [`crate::march`] can run on.
+///
+/// Accesses are grouped into rows so that DRAMs can use fast page mode: the algorithms open a row,
+/// access any number of columns in it, and close it again. Memories without multiplexed addresses
+/// (like SRAMs) simply use the row as the upper part of the address.
+pub trait MemoryUnderTest {
+    /// Brings the chip into a known state after power-up or after it has been swapped.
+    fn init(&mut self) {}
+
+    /// Checks whether a working chip is inserted, and if so, configures the size of the memory
+    /// accordingly.
+    ///
+    /// Returns the name of the detected chip.
+    fn detect(&mut self) -> Result<&'static str, DetectError>;
+
+    /// Number of rows
+    fn rows(&self) -> usize;
+    /// Number of columns per row
+    fn cols(&self) -> usize;
+    /// Number of data bits per column, at most [`MAX_DATA_BITS`]
+    fn data_bits(&self) -> u8;
+
+    /// Position (as in U1, U2, …) of the chip holding data bit `bit`, for memory modules.
+    fn chip_position(&self, _bit: u8) -> Option<u8> {
+        None
+    }
+
+    fn open_row(&mut self, row: usize);
+    fn close_row(&mut self);
+
+    /// Writes `word` to `col` in the currently open row.
+    fn write(&mut self, col: usize, word: u32);
+    /// Reads the word at `col` in the currently open row.
+    fn read(&mut self, col: usize) -> u32;
+
+    /// Runs the march test `test`.
+    // no allocator to box the failure map in, it's only moved once per pass anyway
+    #[allow(clippy::result_large_err)]
+    fn run_test(&mut self, test: &[Element]) -> Result<(), TestError>
+    where
+        Self: Sized,
+    {
+        march::run(self, test)
+    }
+
+    /// Selects the chip type expected by [`MemoryUnderTest::detect`], for memories that support
+    /// them.
+    fn set_chip_type(&mut self, _chip_type: ChipType) {}
+
+    fn chip_type(&self) -> Option<ChipType> {
+        None
+    }
+
+    /// Selects the DRAM timing preset for the following tests, for memories that support them.
+    fn set_timing_preset(&mut self, _preset: TimingPreset) {}
+
+    fn timing_preset(&self) -> Option<TimingPreset> {
+        None
+    }
+
+    /// Measures the access times of the inserted chip, for memories that support it.
+    ///
+    /// Returns `None` if unsupported, or if the data never became valid.
+    fn measure_access_times(&mut self) -> Option<AccessTimes> {
+        None
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DetectError {
+    /// No (working) chip is inserted
+    NoChip,
+    /// The inserted chip doesn't match the selected part
+    WrongChip,
+}
+
+/// Measured access times (ns), corrected for the transceiver delay
+pub struct AccessTimes {
+    /// Access time from RAS low
+    pub t_rac: u32,
+    /// Access time from CAS low
+    pub t_cac: u32,
+    /// How far both may be off, either way
+    pub uncertainty: u32,
+}
+
+pub struct TestError {
+    pub num_failed_bits: usize,
+    /// The step (march element) that found the failed bits, the pass stops after it
+    pub step: usize,
+    pub row: usize,
+    pub col: usize,
+    /// Number of failures per data bit
+    pub fails_per_bit: [usize; MAX_DATA_BITS],
+    pub map: FailureMap,
+}
+
+/// Where in the array the failed cells are, downscaled to [`FailureMap::SIZE`]² bins of rows ×
+/// columns, so that failed rows, columns or quadrants stand out.
+#[derive(Clone)]
+pub struct FailureMap {
+    /// Bit `c % 8` of `bins[r][c / 8]` is set if any cell in bin row `r`, bin column `c` failed
+    bins: [[u8; FailureMap::SIZE.div_ceil(8)]; FailureMap::SIZE],
+    row_shift: u32,
+    col_shift: u32,
+}
+
+impl FailureMap {
+    /// Number of bins per side, one pixel each on the display
+    pub const SIZE: usize = 46;
+
+    /// An empty map of an array with `rows` × `cols` cells, both powers of two.
+    #[inline(always)]
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Self {
+            bins: [[0; Self::SIZE.div_ceil(8)]; Self::SIZE],
+            row_shift: rows.trailing_zeros(),
+            col_shift: cols.trailing_zeros(),
+        }
+    }
+
+    /// Marks the cell at `row`, `col` as failed. Cheap enough for the test loops (no division).
+    #[inline(always)]
+    pub fn mark(&mut self, row: usize, col: usize) {
+        let r = (row * Self::SIZE) >> self.row_shift;
+        let c = (col * Self::SIZE) >> self.col_shift;
+        self.bins[r][c / 8] |= 1 << (c % 8);
+    }
+
+    /// Whether any cell in the bin at `r`, `c` failed
+    pub fn failed(&self, r: usize, c: usize) -> bool {
+        self.bins[r][c / 8] & (1 << (c % 8)) != 0
+    }
+
+    /// The bins, row by row, see [`crate::protocol::Message::Failed`]
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bins.as_flattened()
+    }
+}
@@ -0,0 +1,103 @@
+//! Progress of the running test pass, published by the test engine on core0 and shown by the UI on
+//! core1.
+//!
+//! Only the latest state matters, so it's kept in atomics instead of being queued. Updating it is
+//! a single store, cheap enough for the test loops.
+
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, Ordering};
+
+/// The counter set with [`set_clock`], null until then
+static CLOCK: AtomicPtr<u32> = AtomicPtr::new(core::ptr::null_mut());
+static RUNNING: AtomicBool = AtomicBool::new(false);
+/// Start of the pass (µs, see [`set_clock`])
+static START: AtomicU32 = AtomicU32::new(0);
+static STEP: AtomicU32 = AtomicU32::new(0);
+static STEPS: AtomicU32 = AtomicU32::new(0);
+static ROW: AtomicU32 = AtomicU32::new(0);
+static ROWS: AtomicU32 = AtomicU32::new(0);
+
+/// A snapshot of the progress of the running pass.
+#[derive(Clone, Copy)]
+pub struct Progress {
+    /// Current phase of the test, e.g. the march element
+    pub step: u32,
+    pub steps: u32,
+    /// Number of rows done in the current step
+    pub row: u32,
+    pub rows: u32,
+    pub elapsed_us: u32,
+}
+
+impl Progress {
+    /// Overall progress of the pass, from 0 to `max`
+    pub fn scaled(&self, max: u32) -> u32 {
+        let done = u64::from(self.step) * u64::from(self.rows) + u64::from(self.row);
+        let total = u64::from(self.steps) * u64::from(self.rows);
+        (done * u64::from(max) / total.max(1)) as u32
+    }
+}
+
+/// Starts a pass of `steps` steps over `rows` rows each.
+#[inline(always)]
+pub fn start(steps: usize, rows: usize) {
+    START.store(now_us(), Ordering::Relaxed);
+    STEPS.store(steps as u32, Ordering::Relaxed);
+    ROWS.store(rows as u32, Ordering::Relaxed);
+    STEP.store(0, Ordering::Relaxed);
+    ROW.store(0, Ordering::Relaxed);
+    RUNNING.store(true, Ordering::Release);
+}
+
+/// Moves on to the step `step` (0-based).
+#[inline(always)]
+pub fn step(step: usize) {
+    STEP.store(step as u32, Ordering::Relaxed);
+    ROW.store(0, Ordering::Relaxed);
+}
+
+/// Marks `rows` rows of the current step as done.
+#[inline(always)]
+pub fn rows_done(rows: usize) {
+    ROW.store(rows as u32, Ordering::Relaxed);
+}
+
+#[inline(always)]
+pub fn finish() {
+    RUNNING.store(false, Ordering::Release);
+}
+
+/// The progress of the running pass, if any.
+pub fn get() -> Option<Progress> {
+    if !RUNNING.load(Ordering::Acquire) {
+        return None;
+    }
+
+    Some(Progress {
+        step: STEP.load(Ordering::Relaxed),
+        steps: STEPS.load(Ordering::Relaxed),
+        row: ROW.load(Ordering::Relaxed),
+        rows: ROWS.load(Ordering::Relaxed),
+        elapsed_us: now_us().wrapping_sub(START.load(Ordering::Relaxed)),
+    })
+}
+
+/// Sets the time source of [`Progress::elapsed_us`], a wrapping µs counter such as a timer
+/// register. It's read in place rather than through a function, which the test loops in RAM would
+/// have to call into flash for. Without it, the elapsed time stays 0.
+///
+/// # Safety
+///
+/// `counter` has to stay valid for reads from both cores.
+pub unsafe fn set_clock(counter: *const u32) {
+    CLOCK.store(counter.cast_mut(), Ordering::Release);
+}
+
+#[inline(always)]
+fn now_us() -> u32 {
+    let counter = CLOCK.load(Ordering::Acquire);
+    if counter.is_null() {
+        return 0;
+    }
+    // SAFETY: only `set_clock` stores into `CLOCK`
+    unsafe { counter.read_volatile() }
+}
@@ -0,0 +1,97 @@
+//! Machine-readable events of the tester, streamed by the firmware on the second USB serial port
+//! next to its console.
+//!
+//! Each [`Message`] is serialized with [postcard](https://docs.rs/postcard), COBS-encoded and
+//! terminated by a 0 byte. So a reader can cut the stream into frames at the 0 bytes, and resync
+//! at the next one after connecting in the middle of a message.
+
+use serde::{Deserialize, Serialize};
+
+/// Longest encoded message, including the COBS overhead and the terminating 0 (bytes)
+pub const MAX_FRAME_LEN: usize = 512;
+/// Number of chip positions in [`Message::Failed`], indexed by the number of the position (U1 to
+/// U9 on 9-chip SIMMs)
+pub const CHIP_POSITIONS: usize = 10;
+
+/// The inserted chip
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip<'a> {
+    None,
+    /// Not the selected chip type
+    Wrong,
+    Detected(&'a str),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Message<'a> {
+    /// Sent whenever the inserted chip changes
+    #[serde(borrow)]
+    Chip(Chip<'a>),
+    /// A run was started, with its settings
+    Started {
+        test: &'a str,
+        /// Timing preset, if the memory has any
+        preset: Option<&'a str>,
+        clock_mhz: u16,
+        /// Number of passes, `None` until stopped
+        loops: Option<u32>,
+    },
+    /// The run was stopped, or is done
+    Stopped,
+    /// Progress of the running pass, sent every 100 ms or so
+    Progress {
+        /// Current phase (march element) of the test, 0-based
+        step: u8,
+        steps: u8,
+        /// Rows done in the current step
+        row: u32,
+        rows: u32,
+        elapsed_ms: u32,
+    },
+    /// Result of a phase of the last pass, sent right before [`Message::Passed`] or
+    /// [`Message::Failed`] for each phase that ran. A pass stops after the first failed phase.
+    Phase {
+        step: u8,
+        steps: u8,
+        failed_bits: u32,
+    },
+    Passed {
+        /// Passes in a row
+        pass_count: u32,
+    },
+    Failed {
+        failed_bits: u32,
+        /// Address of the last failed cell
+        last_addr: u32,
+        /// Failed bits per chip position, all 0 if the memory has no chip positions
+        fails_per_chip: [u32; CHIP_POSITIONS],
+        /// Side of the failure map (bins)
+        map_size: u8,
+        /// Failure map, `map_size` rows of `map_size.div_ceil(8)` bytes, bit `c % 8` of byte
+        /// `c / 8` is set if any cell in bin column `c` of the row failed
+        map: &'a [u8],
+    },
+    /// Result of the speed grading: the fastest timing preset the chip passes with and its speed
+    /// grade, `None` if it failed even with the slowest one
+    Graded {
+        preset: Option<&'a str>,
+        grade: Option<&'a str>,
+    },
+    /// Measured access times, `None` without valid data
+    AccessTimes(Option<AccessTimes>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccessTimes {
+    pub t_rac_ns: u32,
+    pub t_cac_ns: u32,
+    /// How far both may be off, either way
+    pub uncertainty_ns: u32,
+}
+
+/// Whether any cell in bin row `r`, bin column `c` of the `map` of [`Message::Failed`] failed
+pub fn map_failed(map: &[u8], map_size: u8, r: usize, c: usize) -> bool {
+    let row_len = usize::from(map_size).div_ceil(8);
+    map.get(r * row_len + c / 8)
+        .is_some_and(|&bins| bins & (1 << (c % 8)) != 0)
+}
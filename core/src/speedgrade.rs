@@ -0,0 +1,42 @@
+//! Automatic speed grading: finds the fastest timing preset a chip still passes with, since
+//! markings on used chips are often wrong or sanded off.
+
+use crate::{
+    march::{self, Element},
+    memory::MemoryUnderTest,
+    timings::TimingPreset,
+};
+
+/// Test used for each preset, short since it's run up to [`TimingPreset::BY_SPEED`]`.len()` times
+pub const TEST: &[Element] = march::MATS_PLUS;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SpeedGrade {
+    /// Fastest preset that still passed
+    Passed(TimingPreset),
+    /// Failed even with the slowest preset
+    Failed,
+    /// The memory doesn't support timing presets
+    Unsupported,
+}
+
+/// Runs [`TEST`] with progressively tighter timing presets, until it fails.
+///
+/// The preset of `mem` is restored afterwards.
+pub fn grade<M: MemoryUnderTest>(mem: &mut M) -> SpeedGrade {
+    let Some(original) = mem.timing_preset() else {
+        return SpeedGrade::Unsupported;
+    };
+
+    let mut grade = SpeedGrade::Failed;
+    for preset in TimingPreset::BY_SPEED {
+        mem.set_timing_preset(preset);
+        if mem.run_test(TEST).is_err() {
+            break;
+        }
+        grade = SpeedGrade::Passed(preset);
+    }
+
+    mem.set_timing_preset(original);
+    grade
+}
@@ -0,0 +1,323 @@
+//! The DRAM timing presets, and the math that turns timings into CPU cycles and checks them.
+//!
+//! The firmware computes all of its delays at compile time with these functions, so they're
+//! tested on the host like the rest of this crate.
+
+/// Minimum timings of a speed grade, as specified in the datasheet (ns)
+#[derive(Clone, Copy, Debug)]
+pub struct NsTimings {
+    /// Pulse duration, RAS low
+    pub t_ras: u32,
+    /// Pulse duration, CAS low
+    pub t_cas: u32,
+    /// RAS low to CAS low delay
+    pub t_rcd: u32,
+    /// Pulse duration, RAS high (precharge)
+    pub t_rp: u32,
+    /// Pulse duration, CAS high (precharge)
+    pub t_cp: u32,
+    /// Access time from RAS low
+    pub t_rac: u32,
+    /// Access time from CAS low
+    pub t_cac: u32,
+    /// Maximum pulse duration, RAS low
+    pub t_ras_max: u32,
+}
+
+impl NsTimings {
+    /// RAS to CAS delay of the firmware: at least tRCD, and long enough that reads aren't limited
+    /// by tRAC, which the read delays don't account for
+    pub const fn ras_to_cas(&self) -> u32 {
+        let rac_limited = self.t_rac.saturating_sub(self.t_cas);
+        if rac_limited > self.t_rcd {
+            rac_limited
+        } else {
+            self.t_rcd
+        }
+    }
+
+    /// Rest of the RAS pulse after CAS went high again
+    pub const fn t_ras_rest(&self) -> u32 {
+        self.t_ras.saturating_sub(self.t_cas + self.ras_to_cas())
+    }
+}
+
+/// Minimum timings of an SRAM, as specified in the datasheet (ns)
+#[derive(Clone, Copy, Debug)]
+pub struct SramNsTimings {
+    /// Access time from address valid
+    pub t_aa: u32,
+    /// Access time from OE low
+    pub t_oe: u32,
+    /// Pulse duration, WE low
+    pub t_wp: u32,
+    /// Write recovery time, from WE high until the address may change
+    pub t_wr: u32,
+}
+
+// Timings of the presets, from the TMS4256 datasheet
+
+pub const NS_80: NsTimings = NsTimings {
+    t_ras: 80,
+    t_cas: 40,
+    t_rcd: 25,
+    t_rp: 70,
+    t_cp: 20,
+    t_rac: 80,
+    t_cac: 40,
+    t_ras_max: 10_000,
+};
+
+pub const NS_100: NsTimings = NsTimings {
+    t_ras: 100,
+    t_cas: 50,
+    t_rcd: 25,
+    t_rp: 90,
+    t_cp: 40,
+    t_rac: 100,
+    t_cac: 50,
+    t_ras_max: 10_000,
+};
+
+pub const NS_120: NsTimings = NsTimings {
+    t_ras: 120,
+    t_cas: 60,
+    t_rcd: 25,
+    t_rp: 90,
+    t_cp: 50,
+    t_rac: 120,
+    t_cac: 60,
+    t_ras_max: 10_000,
+};
+
+pub const NS_150: NsTimings = NsTimings {
+    t_ras: 150,
+    t_cas: 75,
+    t_rcd: 25,
+    t_rp: 100,
+    t_cp: 60,
+    t_rac: 150,
+    t_cac: 75,
+    t_ras_max: 10_000,
+};
+
+/// Timings of the 150 ns SRAMs, from the HM6264 datasheet
+pub const SRAM_150: SramNsTimings = SramNsTimings {
+    t_aa: 150,
+    t_oe: 70,
+    t_wp: 90,
+    t_wr: 10,
+};
+
+/// The DRAM timing configurations that can be selected at runtime.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TimingPreset {
+    /// The firmware's default timings, i.e. the timings of the selected part, if any
+    Default,
+    Dram150Ns,
+    Dram120Ns,
+    Dram100Ns,
+    Dram80Ns,
+}
+
+impl TimingPreset {
+    /// The fixed presets, from slowest to fastest
+    pub const BY_SPEED: [TimingPreset; 4] = [
+        TimingPreset::Dram150Ns,
+        TimingPreset::Dram120Ns,
+        TimingPreset::Dram100Ns,
+        TimingPreset::Dram80Ns,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TimingPreset::Default => "default",
+            TimingPreset::Dram150Ns => "150ns",
+            TimingPreset::Dram120Ns => "120ns",
+            TimingPreset::Dram100Ns => "100ns",
+            TimingPreset::Dram80Ns => "80ns",
+        }
+    }
+
+    /// The timings of a fixed preset, `None` for `Default`, which is up to the firmware
+    pub const fn datasheet(self) -> Option<NsTimings> {
+        match self {
+            TimingPreset::Default => None,
+            TimingPreset::Dram150Ns => Some(NS_150),
+            TimingPreset::Dram120Ns => Some(NS_120),
+            TimingPreset::Dram100Ns => Some(NS_100),
+            TimingPreset::Dram80Ns => Some(NS_80),
+        }
+    }
+
+    /// The speed grade implied by a chip passing with this preset
+    pub fn speed_grade(self) -> Option<&'static str> {
+        match self {
+            TimingPreset::Default => None,
+            TimingPreset::Dram150Ns => Some("-150"),
+            TimingPreset::Dram120Ns => Some("-120"),
+            TimingPreset::Dram100Ns => Some("-100"),
+            TimingPreset::Dram80Ns => Some("-80"),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            TimingPreset::Default => TimingPreset::Dram150Ns,
+            TimingPreset::Dram150Ns => TimingPreset::Dram120Ns,
+            TimingPreset::Dram120Ns => TimingPreset::Dram100Ns,
+            TimingPreset::Dram100Ns => TimingPreset::Dram80Ns,
+            TimingPreset::Dram80Ns => TimingPreset::Default,
+        }
+    }
+}
+
+/// Number of CPU cycles at `freq` (Hz) that last at least `ns` nanoseconds, i.e. rounded up so
+/// that a delay never gets too short.
+pub const fn ns_to_cycles(ns: u32, freq: u32) -> u32 {
+    (ns as u64 * freq as u64).div_ceil(1_000_000_000) as u32
+}
+
+/// What the firmware's DRAM cycles take besides their delays (CPU cycles)
+#[derive(Clone, Copy, Debug)]
+pub struct CycleCosts {
+    /// A pin write, at least
+    pub pin_write: u32,
+    /// An address bus write, at least
+    pub addr_write: u32,
+    /// A page mode access without its delays, with the test loop around it, at most
+    pub access_max: u32,
+    /// From closing a row to reopening it without the delays, recording a failed read in between,
+    /// at most
+    pub row_change_max: u32,
+}
+
+/// Time that `cycles` CPU cycles at `freq` (Hz) last, in ns rounded down
+const fn cycles_to_ns(cycles: u32, freq: u32) -> u64 {
+    cycles as u64 * 1_000_000_000 / freq as u64
+}
+
+/// Cycles from one pin edge to the next one, which the writes in between take `write_cycles` of
+/// (see [`ns_to_cycles`])
+const fn edge_to_edge(ns: u32, freq: u32, write_cycles: u32) -> u32 {
+    let cycles = ns_to_cycles(ns, freq);
+    if cycles > write_cycles {
+        cycles
+    } else {
+        write_cycles
+    }
+}
+
+/// Checks the timings of the firmware's DRAM cycles at `freq`, returns the violated one otherwise.
+///
+/// The pulse widths are made edge to edge from the same values, so they're met by construction.
+/// What's checked are the timings that depend on several delays and on the code around them:
+/// - when a read samples the data, through bus transceivers with a delay of `transceiver` ns, the
+///   data has to be valid after both tCAC and tRAC, with the GPIO writes as short as possible
+/// - a single access has to fit into tRAS(max), see [`max_burst`]
+pub const fn check(
+    timings: &NsTimings,
+    freq: u32,
+    transceiver: u32,
+    costs: &CycleCosts,
+) -> Result<(), &'static str> {
+    let t = timings;
+    let (pin, addr) = (costs.pin_write, costs.addr_write);
+
+    // from CAS falling to sampling after CAS rose and the transceiver delay, the load that samples
+    // takes at least a cycle
+    let cas_to_sample = edge_to_edge(t.t_cas, freq, pin) + ns_to_cycles(transceiver, freq) + 1;
+    if cycles_to_ns(cas_to_sample, freq) < (t.t_cac + transceiver) as u64 {
+        return Err("tCAC isn't met when sampling");
+    }
+    let ras_to_sample = edge_to_edge(t.ras_to_cas(), freq, addr + pin) + cas_to_sample;
+    if cycles_to_ns(ras_to_sample, freq) < (t.t_rac + transceiver) as u64 {
+        return Err("tRAC isn't met when sampling");
+    }
+
+    if max_burst(t, freq, transceiver, costs) == 0 {
+        return Err("tRAS(max) is exceeded by a single access");
+    }
+    Ok(())
+}
+
+/// Number of page mode accesses that fit into one RAS pulse of at most tRAS(max) at `freq`, with
+/// the code around them as slow as possible. The firmware closes and reopens the row after that
+/// many accesses.
+pub const fn max_burst(
+    timings: &NsTimings,
+    freq: u32,
+    transceiver: u32,
+    costs: &CycleCosts,
+) -> u32 {
+    let t = timings;
+    // opening and closing the row, with the code after the last access
+    let fixed =
+        ns_to_cycles(t.ras_to_cas(), freq) + ns_to_cycles(t.t_ras_rest(), freq) + costs.access_max;
+    // a read, writes don't wait for the transceivers
+    let access = ns_to_cycles(t.t_cas, freq)
+        + ns_to_cycles(transceiver, freq)
+        + ns_to_cycles(t.t_cp, freq)
+        + costs.access_max;
+
+    // rounded down, like `cycles_to_ns`
+    let ras_max = (t.t_ras_max as u64 * freq as u64 / 1_000_000_000) as u32;
+    ras_max.saturating_sub(fixed) / access
+}
+
+/// Checks that refreshing one row after every burst of [`max_burst`] accesses keeps up with a chip
+/// that needs a refresh cycle every `refresh_interval` ns, returns what's wrong otherwise.
+///
+/// The firmware refreshes the rows in turn with a RAS-only cycle each time it closes a row, so
+/// from one refresh cycle to the next there are at most the refresh cycle, the change of rows and
+/// the longest RAS pulse of a burst.
+pub const fn check_refresh(
+    timings: &NsTimings,
+    freq: u32,
+    transceiver: u32,
+    costs: &CycleCosts,
+    refresh_interval: u32,
+) -> Result<(), &'static str> {
+    let t = timings;
+    let burst = max_burst(t, freq, transceiver, costs);
+    let ras_low = ns_to_cycles(t.ras_to_cas(), freq)
+        + burst
+            * (ns_to_cycles(t.t_cas, freq)
+                + ns_to_cycles(transceiver, freq)
+                + ns_to_cycles(t.t_cp, freq)
+                + costs.access_max)
+        + ns_to_cycles(t.t_ras_rest(), freq)
+        + costs.access_max;
+    // the precharge before and after the refresh cycle
+    let refresh = ns_to_cycles(t.t_ras, freq) + 2 * ns_to_cycles(t.t_rp, freq);
+
+    let period = ras_low + refresh + costs.row_change_max;
+    // rounded up, unlike `cycles_to_ns`
+    if (period as u64 * 1_000_000_000).div_ceil(freq as u64) > refresh_interval as u64 {
+        return Err("refresh can't keep up with the bursts");
+    }
+    Ok(())
+}
+
+/// Checks the timings of the firmware's SRAM cycles at `freq` like [`check`]: a read samples the
+/// data `t_aa` plus the `transceiver` delay after the address is valid, which has to be after tOE
+/// too, as OE falls later. The address is latched before OE falls, but only valid up to
+/// `addr_delay` after it, the rest of its propagation delay.
+pub const fn check_sram(
+    timings: &SramNsTimings,
+    freq: u32,
+    transceiver: u32,
+    addr_delay: u32,
+    costs: &CycleCosts,
+) -> Result<(), &'static str> {
+    let t = timings;
+
+    // from OE falling, the delay after the writes to RCLK and OE, and the sampling load
+    let oe_to_sample = ns_to_cycles(addr_delay + t.t_aa + transceiver, freq)
+        .saturating_sub(2 * costs.pin_write)
+        + 1;
+    if cycles_to_ns(oe_to_sample, freq) < (t.t_oe + transceiver) as u64 {
+        return Err("tOE isn't met when sampling");
+    }
+    Ok(())
+}
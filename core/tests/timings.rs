@@ -0,0 +1,167 @@
+//! The timing math that the firmware's delays are computed with.
+
+use picoram_core::{
+    chipdb,
+    timings::{self, CycleCosts, TimingPreset, NS_150, SRAM_150},
+};
+
+/// The system clocks of the firmware (Hz)
+const CLOCKS: [u32; 2] = [125_000_000, 150_000_000];
+
+/// Like the firmware's
+const COSTS: CycleCosts = CycleCosts {
+    pin_write: 1,
+    addr_write: 2,
+    access_max: 80,
+    row_change_max: 250,
+};
+
+const TRANSCEIVER: u32 = 14;
+
+/// Like the firmware's: the 74HCT595's 50 ns from RCLK to the address, less the 25 ns RCLK pulse
+const SRAM_ADDR_DELAY: u32 = 25;
+
+#[test]
+fn ns_to_cycles_rounds_up() {
+    assert_eq!(timings::ns_to_cycles(0, 125_000_000), 0);
+    assert_eq!(timings::ns_to_cycles(80, 125_000_000), 10);
+    assert_eq!(timings::ns_to_cycles(75, 125_000_000), 10);
+    assert_eq!(timings::ns_to_cycles(1, 300_000_000), 1);
+    assert_eq!(timings::ns_to_cycles(1_000_000, 300_000_000), 300_000);
+}
+
+#[test]
+fn presets_are_valid_at_all_clocks() {
+    for freq in CLOCKS {
+        for preset in TimingPreset::BY_SPEED {
+            let datasheet = preset.datasheet().unwrap();
+            assert_eq!(
+                timings::check(&datasheet, freq, TRANSCEIVER, &COSTS),
+                Ok(()),
+                "{preset:?}"
+            );
+            assert_eq!(
+                timings::check_refresh(
+                    &datasheet,
+                    freq,
+                    TRANSCEIVER,
+                    &COSTS,
+                    chipdb::MIN_REFRESH_INTERVAL
+                ),
+                Ok(()),
+                "{preset:?}"
+            );
+        }
+        for part in chipdb::PARTS {
+            for grade in part.grades {
+                assert_eq!(
+                    timings::check(&grade.timings, freq, TRANSCEIVER, &COSTS),
+                    Ok(()),
+                    "{}{}",
+                    part.name,
+                    grade.suffix
+                );
+                assert_eq!(
+                    timings::check_refresh(
+                        &grade.timings,
+                        freq,
+                        TRANSCEIVER,
+                        &COSTS,
+                        part.refresh_interval()
+                    ),
+                    Ok(()),
+                    "{}{}",
+                    part.name,
+                    grade.suffix
+                );
+                // the PIO driver needs at least 8
+                assert!(
+                    timings::max_burst(&grade.timings, freq, TRANSCEIVER, &COSTS) >= 8,
+                    "{}{}",
+                    part.name,
+                    grade.suffix
+                );
+            }
+        }
+        assert_eq!(
+            timings::check_sram(&SRAM_150, freq, TRANSCEIVER, SRAM_ADDR_DELAY, &COSTS),
+            Ok(())
+        );
+    }
+    assert!(TimingPreset::Default.datasheet().is_none());
+}
+
+#[test]
+fn refresh_intervals() {
+    assert_eq!(chipdb::TMS4164.refresh_interval(), 15_625);
+    assert_eq!(chipdb::MK4564.refresh_interval(), 15_625);
+    assert_eq!(chipdb::MIN_REFRESH_INTERVAL, 15_625);
+}
+
+#[test]
+fn reads_wait_for_rac() {
+    // 25 ns tRCD and 75 ns tCAS would sample at 100 ns
+    assert_eq!(NS_150.ras_to_cas(), 75);
+    assert_eq!(NS_150.t_ras_rest(), 0);
+}
+
+#[test]
+fn bursts_fit_into_ras_max() {
+    for freq in CLOCKS {
+        for preset in TimingPreset::BY_SPEED {
+            let t = preset.datasheet().unwrap();
+            let burst = timings::max_burst(&t, freq, TRANSCEIVER, &COSTS);
+            assert!(burst >= 8, "{preset:?} at {freq} Hz");
+
+            let cycles = |ns| timings::ns_to_cycles(ns, freq);
+            let access = cycles(t.t_cas) + cycles(TRANSCEIVER) + cycles(t.t_cp) + COSTS.access_max;
+            let ras_low =
+                cycles(t.ras_to_cas()) + burst * access + cycles(t.t_ras_rest()) + COSTS.access_max;
+            assert!(u64::from(ras_low) * 1_000_000_000 / u64::from(freq) <= u64::from(t.t_ras_max));
+        }
+    }
+    // 100 cycles of 8 ns per access
+    assert_eq!(
+        timings::max_burst(&NS_150, 125_000_000, TRANSCEIVER, &COSTS),
+        11
+    );
+}
+
+#[test]
+fn impossible_timings_are_rejected() {
+    // CAS shorter than its access time
+    let short_cas = timings::NsTimings {
+        t_cas: 60,
+        ..NS_150
+    };
+    assert_eq!(
+        timings::check(&short_cas, 125_000_000, TRANSCEIVER, &COSTS),
+        Err("tCAC isn't met when sampling")
+    );
+
+    // a single access at the slowest clock already keeps RAS low for ~1.3 µs
+    let short_ras_max = timings::NsTimings {
+        t_ras_max: 700,
+        ..NS_150
+    };
+    assert_eq!(
+        timings::check(&short_ras_max, 125_000_000, TRANSCEIVER, &COSTS),
+        Err("tRAS(max) is exceeded by a single access")
+    );
+
+    // a 64K chip with 256 rows refreshed within 1 ms would need a refresh cycle every 3.9 µs
+    assert_eq!(
+        timings::check_refresh(&NS_150, 125_000_000, TRANSCEIVER, &COSTS, 3_906),
+        Err("refresh can't keep up with the bursts")
+    );
+
+    // OE access time longer than the address access time and the shift register's delay
+    let slow_oe = timings::SramNsTimings {
+        t_oe: 200,
+        ..SRAM_150
+    };
+    assert_eq!(
+        timings::check_sram(&slow_oe, 125_000_000, TRANSCEIVER, SRAM_ADDR_DELAY, &COSTS),
+        Err("tOE isn't met when sampling")
+    );
+}
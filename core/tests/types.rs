@@ -0,0 +1,142 @@
+//! The result, timing, progress and protocol types and the console commands, without a memory.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use picoram_core::{
+    chipdb::{self, ChipType},
+    console::Request,
+    march::Test,
+    memory::{DetectError, FailureMap},
+    progress,
+    protocol::{self, Message},
+    timings::TimingPreset,
+};
+
+#[test]
+fn failure_map_bytes_match_protocol() {
+    let mut map = FailureMap::new(256, 512);
+    map.mark(0, 0);
+    map.mark(255, 511);
+    map.mark(128, 100);
+
+    let size = FailureMap::SIZE as u8;
+    for r in 0..FailureMap::SIZE {
+        for c in 0..FailureMap::SIZE {
+            assert_eq!(
+                protocol::map_failed(map.as_bytes(), size, r, c),
+                map.failed(r, c),
+                "bin {r}, {c}"
+            );
+        }
+    }
+    assert!(map.failed(0, 0));
+    assert!(map.failed(FailureMap::SIZE - 1, FailureMap::SIZE - 1));
+    assert!(map.failed(23, 8));
+}
+
+#[test]
+fn failed_message_fits_a_frame() {
+    let map = FailureMap::new(512, 512);
+    let message = Message::Failed {
+        failed_bits: u32::MAX,
+        last_addr: u32::MAX,
+        fails_per_chip: [u32::MAX; protocol::CHIP_POSITIONS],
+        map_size: FailureMap::SIZE as u8,
+        map: map.as_bytes(),
+    };
+    let mut buf = [0; protocol::MAX_FRAME_LEN];
+    let frame = postcard::to_slice_cobs(&message, &mut buf).unwrap();
+    let decoded: Message = postcard::from_bytes_cobs(frame).unwrap();
+    assert_eq!(decoded, message);
+}
+
+#[test]
+fn chip_types() {
+    let selected = ChipType::selected(Some(chipdb::select(&chipdb::TMS4164, "-15")));
+    assert_eq!(selected.name(), "TMS4164");
+    assert_eq!(selected.check(8, "4164"), Ok("TMS4164"));
+    assert_eq!(selected.check(9, "41256"), Err(DetectError::WrongChip));
+    assert_eq!(ChipType::selected(None).check(9, "41256"), Ok("41256"));
+
+    // all parts, then back to auto
+    let mut chip_type = ChipType::Auto;
+    for _ in 0..chipdb::PARTS.len() {
+        chip_type = chip_type.next();
+        assert!(matches!(chip_type, ChipType::Part(_)));
+    }
+    assert!(matches!(chip_type.next(), ChipType::Auto));
+}
+
+#[test]
+fn selected_parts_cycle() {
+    // like the firmware's `PART`
+    const PART: Option<chipdb::Selection> = Some(chipdb::select(&chipdb::MK4564, "-15"));
+    assert_eq!(ChipType::selected(PART).next().name(), "HM4864");
+
+    for (i, part) in chipdb::PARTS.iter().enumerate() {
+        for grade in part.grades {
+            let selected = ChipType::selected(Some(chipdb::select(part, grade.suffix)));
+            let expected = chipdb::PARTS.get(i + 1).map_or("auto", |next| next.name);
+            assert_eq!(
+                selected.next().name(),
+                expected,
+                "{}{}",
+                part.name,
+                grade.suffix
+            );
+        }
+    }
+}
+
+#[test]
+fn timing_presets_cycle() {
+    let mut preset = TimingPreset::Default;
+    for expected in TimingPreset::BY_SPEED {
+        preset = preset.next();
+        assert_eq!(preset, expected);
+    }
+    assert_eq!(preset.next(), TimingPreset::Default);
+}
+
+#[test]
+fn console_requests() {
+    assert_eq!(
+        Request::parse("run marchc-"),
+        Ok(Request::Run(Test::MarchCMinus))
+    );
+    assert_eq!(
+        Request::parse("set timing 100NS"),
+        Ok(Request::SetTiming(TimingPreset::Dram100Ns))
+    );
+    assert_eq!(
+        Request::parse("  set loops 3 "),
+        Ok(Request::SetLoops(Some(3)))
+    );
+    assert_eq!(
+        Request::parse("set loops forever"),
+        Ok(Request::SetLoops(None))
+    );
+
+    assert_eq!(Request::parse("run bogus"), Err("unknown test"));
+    assert!(Request::parse("set loops 0").is_err());
+    assert!(Request::parse("set loops -1").is_err());
+    assert_eq!(Request::parse("stop now"), Err("too many arguments"));
+    assert!(Request::parse("frobnicate").is_err());
+}
+
+#[test]
+fn progress_is_timed() {
+    static COUNTER: AtomicU32 = AtomicU32::new(1_000);
+    unsafe { progress::set_clock(COUNTER.as_ptr()) };
+
+    progress::start(2, 10);
+    progress::step(1);
+    progress::rows_done(5);
+    COUNTER.store(1_250, Ordering::Relaxed);
+    let progress = progress::get().unwrap();
+    assert_eq!(progress.elapsed_us, 250);
+    assert_eq!(progress.scaled(100), 75);
+
+    progress::finish();
+    assert!(progress::get().is_none());
+}
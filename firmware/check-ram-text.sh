@@ -0,0 +1,39 @@
+#!/bin/sh
+# Checks that the timing-critical code linked into RAM (`.ram_text`, see `memory.x`) doesn't call
+# into flash, where it could be stalled by XIP cache misses.
+#
+# Flash is out of range for `bl` from RAM, so the linker routes such calls through long branch
+# thunks, which are placed next to the caller. Any thunk in RAM is a call from RAM into flash.
+# Calls through a register (`blx`, or `bx` other than returns), e.g. of function pointers, may go
+# anywhere, so any of them in RAM is reported too. Panics are ignored, as they end the test anyway.
+#
+# Usage: ./check-ram-text.sh [ELF], defaults to the release build. Needs `rust-nm` and
+# `rust-objdump` from cargo-binutils, or others in $NM and $OBJDUMP.
+
+set -eu
+
+elf=${1:-target/thumbv6m-none-eabi/release/picoram}
+nm=${NM:-rust-nm}
+objdump=${OBJDUMP:-rust-objdump}
+
+thunks=$($nm "$elf" | awk '$1 ~ /^2/ && $3 ~ /LongThunk_/ && $3 !~ /panic/ { print $3 }')
+
+indirect=$($objdump -d --no-show-raw-insn -j .ram_text "$elf" | awk '
+    /^[0-9a-f]+ <.*>:$/ { function_name = substr($2, 2, length($2) - 3) }
+    ($2 == "blx" || ($2 == "bx" && $3 != "lr")) && function_name !~ /panic/ {
+        print function_name ": " $2 " " $3
+    }')
+
+if [ -n "$thunks" ]; then
+    echo "code in RAM calls into flash through:"
+    echo "$thunks"
+fi
+if [ -n "$indirect" ]; then
+    echo "code in RAM calls through registers in:"
+    echo "$indirect"
+fi
+if [ -n "$thunks$indirect" ]; then
+    exit 1
+fi
+
+echo "no calls from RAM into flash"
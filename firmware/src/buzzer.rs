@@ -0,0 +1,117 @@
+//! Piezo buzzer, so that the results can be heard without watching the display, see
+//! [`crate::BUZZER`].
+//!
+//! The [`Tone`]s are played without blocking, as long as [`Buzzer::update`] is called often enough
+//! (every few ms, e.g. from the UI loop).
+
+use embedded_hal::PwmPin;
+
+use crate::{
+    clocks,
+    hal::{
+        gpio::{bank0::Gpio19, FunctionPwm, Pin, PinId},
+        pwm::{FreeRunning, Pwm1, Slice},
+    },
+    progress,
+};
+
+/// Rate the PWM counter runs at, independent of the system clock (Hz)
+const COUNTER_FREQ: u32 = 2_000_000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Tone {
+    /// A chip was inserted
+    Detected,
+    Passed,
+    /// Also for chips of the wrong type
+    Failed,
+    /// The chip was taken out
+    Removed,
+}
+
+impl Tone {
+    /// Notes of (frequency in Hz, length in ms), a pause at 0 Hz
+    fn notes(self) -> &'static [(u32, u32)] {
+        match self {
+            // short chirp
+            Tone::Detected => &[(2000, 50)],
+            // rising
+            Tone::Passed => &[(1500, 80), (0, 30), (3000, 200)],
+            // long and low
+            Tone::Failed => &[(400, 600)],
+            // falling
+            Tone::Removed => &[(3000, 50), (0, 30), (1500, 50)],
+        }
+    }
+}
+
+/// The buzzer on GPIO19, driven by PWM slice 1. Without one, playing does nothing.
+pub struct Buzzer {
+    pwm: Option<(Slice<Pwm1, FreeRunning>, Pin<Gpio19, FunctionPwm>)>,
+    /// Rest of the tone being played, starting with the current note
+    notes: &'static [(u32, u32)],
+    note_start_us: u32,
+}
+
+impl Buzzer {
+    pub const NONE: Buzzer = Buzzer {
+        pwm: None,
+        notes: &[],
+        note_start_us: 0,
+    };
+
+    pub fn new(
+        mut slice: Slice<Pwm1, FreeRunning>,
+        pin: Pin<Gpio19, <Gpio19 as PinId>::Reset>,
+    ) -> Self {
+        let pin = slice.channel_b.output_to(pin);
+        slice.channel_b.set_duty(0);
+        slice.enable();
+        Self {
+            pwm: Some((slice, pin)),
+            ..Self::NONE
+        }
+    }
+
+    /// Starts playing `tone`, cutting off the one before.
+    pub fn play(&mut self, tone: Tone) {
+        if self.pwm.is_some() {
+            self.notes = tone.notes();
+            self.start_note();
+        }
+    }
+
+    /// Moves on to the next note when it's time to, at `now_us` (µs, see
+    /// [`crate::progress::now_us`]).
+    pub fn update(&mut self, now_us: u32) {
+        let Some(&(_, length_ms)) = self.notes.first() else {
+            return;
+        };
+        if now_us.wrapping_sub(self.note_start_us) >= length_ms * 1000 {
+            self.notes = &self.notes[1..];
+            self.start_note();
+        }
+    }
+
+    /// Sounds the first of [`Self::notes`], or silences the buzzer after the last one.
+    fn start_note(&mut self) {
+        let Some((slice, _)) = &mut self.pwm else {
+            return;
+        };
+        self.note_start_us = progress::now_us();
+
+        match self.notes.first() {
+            Some(&(freq, _)) if freq > 0 => {
+                // the system clock can be switched at runtime, 8.4 fixed point
+                let divisor = clocks::current().freq() / (COUNTER_FREQ / 16);
+                slice.set_div_int((divisor >> 4) as u8);
+                slice.set_div_frac((divisor & 0xf) as u8);
+
+                let top = (COUNTER_FREQ / freq - 1) as u16;
+                slice.set_top(top);
+                slice.channel_b.set_duty(top / 2);
+            }
+            _ => slice.channel_b.set_duty(0),
+        }
+    }
+}
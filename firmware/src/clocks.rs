@@ -0,0 +1,246 @@
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use cortex_m::peripheral::SYST;
+
+use super::pac;
+use crate::delay::{self, SelfTestError};
+use fugit::{HertzU32, RateExtU32};
+use rp_pico::hal::{
+    clocks::{Clock, ClockSource, ClocksManager, InitError, UsbClock},
+    pll::{common_configs::PLL_USB_48MHZ, setup_pll_blocking, Locked, PLLConfig, PhaseLockedLoop},
+    xosc::{setup_xosc_blocking, CrystalOscillator, Stable},
+    Watchdog,
+};
+
+use rp_pico::hal::pll::common_configs::PLL_SYS_125MHZ;
+
+/// The system clock frequencies that can be selected at runtime.
+///
+/// The RP2040 is only specified up to 133 MHz, and runs at 150 MHz with the default core voltage
+/// and flash divider. Faster clocks would need a higher VREG voltage and a slower QSPI clock, so
+/// they aren't offered.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ClockPreset {
+    Mhz125,
+    Mhz150,
+}
+
+impl ClockPreset {
+    const ALL: [ClockPreset; 2] = [ClockPreset::Mhz125, ClockPreset::Mhz150];
+
+    pub const fn freq(self) -> u32 {
+        match self {
+            ClockPreset::Mhz125 => 125_000_000,
+            ClockPreset::Mhz150 => 150_000_000,
+        }
+    }
+
+    pub const fn pll(self) -> PLLConfig {
+        match self {
+            ClockPreset::Mhz125 => PLL_SYS_125MHZ,
+            ClockPreset::Mhz150 => PLLConfig {
+                vco_freq: HertzU32::MHz(1500),
+                refdiv: 1,
+                post_div1: 5,
+                post_div2: 2,
+            },
+        }
+    }
+
+    /// Length of a cycle, rounded down (ns)
+    pub const fn ns_per_cycle(self) -> u32 {
+        1_000_000_000 / self.freq()
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ClockPreset::Mhz125 => "125MHz",
+            ClockPreset::Mhz150 => "150MHz",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+}
+
+/// A system clock frequency known at compile time, so that delays can be computed at compile time
+/// (see [`with_clock`]).
+pub trait SysClock {
+    const PRESET: ClockPreset;
+    const FREQ: u32 = Self::PRESET.freq();
+    /// Length of a cycle, rounded down (ns)
+    const NS_PER_CYCLE: u32 = Self::PRESET.ns_per_cycle();
+}
+
+pub struct Clock125;
+impl SysClock for Clock125 {
+    const PRESET: ClockPreset = ClockPreset::Mhz125;
+}
+
+pub struct Clock150;
+impl SysClock for Clock150 {
+    const PRESET: ClockPreset = ClockPreset::Mhz150;
+}
+
+/// The fastest clock preset. Delays outside of [`with_clock`] are computed for it, so they're
+/// never too short, only longer than needed at slower clocks.
+pub type Fastest = Clock150;
+
+/// Evaluates `$body` with `$clock` being the [`SysClock`] of the [`ClockPreset`] `$preset`, see
+/// [`with_preset`](crate::timings::with_preset).
+macro_rules! with_clock {
+    ($preset:expr, $clock:ident => $body:expr) => {
+        match $preset {
+            $crate::clocks::ClockPreset::Mhz125 => {
+                type $clock = $crate::clocks::Clock125;
+                $body
+            }
+            $crate::clocks::ClockPreset::Mhz150 => {
+                type $clock = $crate::clocks::Clock150;
+                $body
+            }
+        }
+    };
+}
+pub(crate) use with_clock;
+
+static CURRENT: AtomicU8 = AtomicU8::new(crate::CLOCK as u8);
+
+/// The currently active clock preset
+pub fn current() -> ClockPreset {
+    ClockPreset::ALL[usize::from(CURRENT.load(Ordering::Relaxed))]
+}
+
+/// The clocks, keeping the system PLL around to be able to switch the clock at runtime.
+pub struct Clocks {
+    pub manager: ClocksManager,
+    xosc: CrystalOscillator<Stable>,
+    pll_sys: Option<PhaseLockedLoop<Locked, pac::PLL_SYS>>,
+    usb_clock_taken: bool,
+    /// For checking the delays again after reclocking, see [`Clocks::self_test`]
+    syst: Option<SYST>,
+}
+
+impl Clocks {
+    /// The USB clock, for the USB peripheral. Can only be taken once.
+    ///
+    /// Taking it out of the [`ClocksManager`] would keep the system clock from being switched.
+    pub fn take_usb_clock(&mut self) -> Option<UsbClock> {
+        if core::mem::replace(&mut self.usb_clock_taken, true) {
+            return None;
+        }
+        // the USB peripheral only takes it as proof that the clock runs, and reclocking never
+        // touches it
+        Some(unsafe { core::ptr::read(&self.manager.usb_clock) })
+    }
+
+    /// Checks that the delays take as long as computed at the current clock, see
+    /// [`delay::self_test`]. Keeps `syst` to check them again whenever the clock is switched.
+    pub fn self_test(&mut self, syst: SYST) -> Result<(), SelfTestError> {
+        self.syst = Some(syst);
+        self.check_delays()
+    }
+
+    fn check_delays(&mut self) -> Result<(), SelfTestError> {
+        match &mut self.syst {
+            Some(syst) => with_clock!(current(), C => delay::self_test::<{ C::FREQ }>(syst)),
+            None => Ok(()),
+        }
+    }
+
+    /// Switches the system clock to `preset`, and repeats the [self-test](Self::self_test) at the
+    /// new clock.
+    ///
+    /// Peripherals are clocked from the USB PLL, so they aren't affected. Running PIO programs
+    /// are, though.
+    pub fn reclock(&mut self, preset: ClockPreset) -> Result<(), SelfTestError> {
+        if preset == current() {
+            return Ok(());
+        }
+
+        let mut resets = unsafe { pac::Peripherals::steal() }.RESETS;
+        let pll_sys = self.pll_sys.take().unwrap().free();
+
+        // switches the system clock to the reference clock while the PLL is reconfigured
+        let pll_sys = setup_pll_blocking(
+            pll_sys,
+            self.xosc.operating_frequency(),
+            preset.pll(),
+            &mut self.manager,
+            &mut resets,
+        )
+        .unwrap();
+
+        self.manager
+            .reference_clock
+            .configure_clock(&self.xosc, self.xosc.get_freq())
+            .unwrap();
+        self.manager
+            .system_clock
+            .configure_clock(&pll_sys, pll_sys.get_freq())
+            .unwrap();
+
+        self.pll_sys = Some(pll_sys);
+        CURRENT.store(preset as u8, Ordering::Relaxed);
+        self.check_delays()
+    }
+}
+
+/// Initialize the clocks and plls according to the reference implementation
+#[allow(clippy::too_many_arguments)]
+pub fn init_clocks_and_plls(
+    preset: ClockPreset,
+    xosc_crystal_freq: u32,
+    xosc_dev: pac::XOSC,
+    clocks_dev: pac::CLOCKS,
+    pll_sys_dev: pac::PLL_SYS,
+    pll_usb_dev: pac::PLL_USB,
+    resets: &mut pac::RESETS,
+    watchdog: &mut Watchdog,
+) -> Result<Clocks, InitError> {
+    let xosc = setup_xosc_blocking(xosc_dev, xosc_crystal_freq.Hz()).map_err(InitError::XoscErr)?;
+
+    // Configure watchdog tick generation to tick over every microsecond
+    watchdog.enable_tick_generation((xosc_crystal_freq / 1_000_000) as u8);
+
+    let mut clocks = ClocksManager::new(clocks_dev);
+
+    let pll_sys = setup_pll_blocking(
+        pll_sys_dev,
+        xosc.operating_frequency(),
+        preset.pll(),
+        &mut clocks,
+        resets,
+    )
+    .map_err(InitError::PllError)?;
+
+    let pll_usb = setup_pll_blocking(
+        pll_usb_dev,
+        xosc.operating_frequency(),
+        PLL_USB_48MHZ,
+        &mut clocks,
+        resets,
+    )
+    .map_err(InitError::PllError)?;
+
+    clocks
+        .init_default(&xosc, &pll_sys, &pll_usb)
+        .map_err(InitError::ClockError)?;
+
+    // run the peripherals from the USB PLL, so that they don't change speed when reclocking
+    clocks
+        .peripheral_clock
+        .configure_clock(&pll_usb, pll_usb.get_freq())
+        .map_err(InitError::ClockError)?;
+
+    CURRENT.store(preset as u8, Ordering::Relaxed);
+
+    Ok(Clocks {
+        manager: clocks,
+        xosc,
+        pll_sys: Some(pll_sys),
+        usb_clock_taken: false,
+        syst: None,
+    })
+}
@@ -0,0 +1,182 @@
+//! Serial console over USB (CDC-ACM), so that the tester can be driven from any computer without a
+//! debug probe.
+//!
+//! It takes one command per line, see [`Request`], and answers in plain text. The console is
+//! serviced by the UI on core1, which knows the state of the tester from the engine's events.
+//!
+//! The USB device has a second serial port, which streams the events for tools instead, see
+//! [`crate::protocol`].
+
+use core::convert::Infallible;
+
+use ufmt::{uWrite, uwrite};
+use usb_device::{class_prelude::UsbBusAllocator, prelude::*, UsbError};
+use usbd_serial::SerialPort;
+
+pub use picoram_core::console::{Request, HELP};
+
+use crate::{
+    hal::usb::UsbBus,
+    progress,
+    protocol::{Message, MAX_FRAME_LEN},
+};
+
+/// Longest command line (bytes)
+const LINE_LEN: usize = 32;
+/// How long writing waits for the host to read, before dropping the output (µs)
+const WRITE_TIMEOUT_US: u32 = 100_000;
+
+pub struct Console {
+    device: UsbDevice<'static, UsbBus>,
+    serial: SerialPort<'static, UsbBus>,
+    /// Port of the [`Message`] stream
+    stream: SerialPort<'static, UsbBus>,
+    line: heapless::String<LINE_LEN>,
+    /// Whether the line got too long, it's dropped then
+    overflow: bool,
+    /// Whether the last byte was a `\r`
+    after_cr: bool,
+}
+
+impl Console {
+    pub fn new(bus: &'static UsbBusAllocator<UsbBus>) -> Self {
+        let serial = SerialPort::new(bus);
+        let stream = SerialPort::new(bus);
+        // pid.codes test PID for CDC-ACM devices
+        let device = UsbDeviceBuilder::new(bus, UsbVidPid(0x16c0, 0x27dd))
+            .manufacturer("picoram")
+            .product("picoram memory tester")
+            .serial_number("picoram")
+            // composite device of two ports, with interface association descriptors
+            .device_class(0xef)
+            .device_sub_class(0x02)
+            .device_protocol(0x01)
+            .build();
+        Self {
+            device,
+            serial,
+            stream,
+            line: heapless::String::new(),
+            overflow: false,
+            after_cr: false,
+        }
+    }
+
+    /// Services the USB device, returns the request once a line is complete. Echoes the input,
+    /// and answers lines that aren't valid requests right away.
+    pub fn poll(&mut self) -> Option<Request> {
+        self.device.poll(&mut [&mut self.serial, &mut self.stream]);
+        // nothing is received on the stream
+        let _ = self.stream.read(&mut [0; 16]);
+
+        // one byte at a time, the rest stays buffered for the next line
+        let mut b = 0;
+        while let Ok(1) = self.serial.read(core::slice::from_mut(&mut b)) {
+            let after_cr = core::mem::replace(&mut self.after_cr, b == b'\r');
+            match b {
+                // the `\n` of `\r\n`
+                b'\n' if after_cr => {}
+                b'\r' | b'\n' => {
+                    self.echo("\n");
+                    let line = core::mem::take(&mut self.line);
+                    let request = if core::mem::take(&mut self.overflow) {
+                        Err("line too long")
+                    } else if line.trim().is_empty() {
+                        self.prompt();
+                        continue;
+                    } else {
+                        Request::parse(&line)
+                    };
+
+                    match request {
+                        Ok(request) => return Some(request),
+                        Err(message) => {
+                            let _ = uwrite!(self, "error: {}\n", message);
+                            self.prompt();
+                        }
+                    }
+                }
+                // backspace or delete
+                0x08 | 0x7f => {
+                    if self.line.pop().is_some() {
+                        self.echo("\x08 \x08");
+                    }
+                }
+                b' '..=b'~' => {
+                    if self.line.push(char::from(b)).is_ok() {
+                        self.echo(core::str::from_utf8(&[b]).unwrap());
+                    } else {
+                        self.overflow = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Asks for the next command.
+    pub fn prompt(&mut self) {
+        let _ = self.write_str("> ");
+    }
+
+    fn echo(&mut self, s: &str) {
+        let _ = self.write_str(s);
+    }
+
+    /// Sends `message` on the stream.
+    pub fn send(&mut self, message: &Message) {
+        let mut buf = [0; MAX_FRAME_LEN];
+        match postcard::to_slice_cobs(message, &mut buf) {
+            Ok(frame) => {
+                write_bytes(&mut self.device, &mut self.stream, &mut self.serial, frame);
+            }
+            Err(_) => defmt::warn!("message too long for a frame"),
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        write_bytes(&mut self.device, &mut self.serial, &mut self.stream, bytes);
+    }
+}
+
+/// Writes `bytes` to `port` as they are, dropping them if nobody reads them in time. Keeps the
+/// `other` port serviced meanwhile.
+fn write_bytes(
+    device: &mut UsbDevice<'static, UsbBus>,
+    port: &mut SerialPort<'static, UsbBus>,
+    other: &mut SerialPort<'static, UsbBus>,
+    mut bytes: &[u8],
+) {
+    let start = progress::now_us();
+    // with the port closed, nobody is listening
+    while !bytes.is_empty() && port.dtr() {
+        match port.write(bytes) {
+            Ok(n) => bytes = &bytes[n..],
+            Err(UsbError::WouldBlock) => {
+                if progress::now_us().wrapping_sub(start) >= WRITE_TIMEOUT_US {
+                    return;
+                }
+                device.poll(&mut [&mut *port, &mut *other]);
+            }
+            Err(_) => return,
+        }
+    }
+}
+
+/// Writes text, with `\n` turned into `\r\n` for terminals.
+impl uWrite for Console {
+    type Error = Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Infallible> {
+        let mut lines = s.split('\n');
+        if let Some(first) = lines.next() {
+            self.write_bytes(first.as_bytes());
+        }
+        for line in lines {
+            self.write_bytes(b"\r\n");
+            self.write_bytes(line.as_bytes());
+        }
+        Ok(())
+    }
+}
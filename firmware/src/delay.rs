@@ -0,0 +1,212 @@
+//! High-accuracy delay functions.
+
+use core::{arch::asm, hint::black_box, sync::atomic::compiler_fence};
+
+use cortex_m::peripheral::{syst::SystClkSource, SYST};
+
+use crate::{pac, timings::PIN_WRITE_CYCLES};
+
+/// Blocks the program for `NS` nanoseconds (+ up to 1 cycle).
+#[inline(always)]
+pub fn delay_ns<const NS: u32, const SYSTEM_FREQ: u32>() {
+    delay_ns_const(NS, SYSTEM_FREQ);
+}
+
+/// Same as [`delay_ns`], but takes the delay as regular arguments, for values that can't be used
+/// as const generic arguments (like associated consts of a generic type).
+///
+/// Both arguments have to be constants (after inlining), otherwise the delay is way off.
+#[inline(always)]
+pub fn delay_ns_const(ns: u32, system_freq: u32) {
+    delay_cycles_const(ns_to_cycles(ns, system_freq));
+}
+
+/// Like [`delay_ns_const`], but makes `ns` the time from the previous pin edge to the next one,
+/// with `overhead_cycles` being the cycles that the code between them takes without any delay.
+///
+/// All arguments have to be constants (after inlining), otherwise the delay is way off.
+#[inline(always)]
+pub fn delay_edge_to_edge(ns: u32, system_freq: u32, overhead_cycles: u32) {
+    delay_cycles_const(ns_to_cycles(ns, system_freq).saturating_sub(overhead_cycles));
+}
+
+/// Blocks the program for `cycles` CPU cycles, `cycles` has to be a constant (after inlining).
+#[inline(always)]
+pub fn delay_cycles_const(cycles: u32) {
+    // don't let the compiler reorder the delay loop
+    compiler_fence(core::sync::atomic::Ordering::SeqCst);
+
+    let loop_count = cycles / 3;
+    let rest_cycles = cycles % 3;
+
+    if loop_count > 0 {
+        delay_loop_3cyc(loop_count);
+    }
+
+    match rest_cycles {
+        0 => {}
+        1 => {
+            nop();
+        }
+        2 => {
+            nop();
+            nop();
+        }
+        _ => unsafe {
+            core::hint::unreachable_unchecked();
+        },
+    }
+
+    // don't let the compiler reorder the delay loop
+    compiler_fence(core::sync::atomic::Ordering::SeqCst);
+}
+
+pub use picoram_core::timings::ns_to_cycles;
+
+/// Maximum number of cycles of [`delay_cycles`]
+pub const MAX_VARIABLE_CYCLES: u32 = 64;
+
+/// [`MAX_VARIABLE_CYCLES`] nops, one per line. Unlike `.rept`, this lets the compiler see the size
+/// of the sled, which it needs for placing literal pools within reach.
+macro_rules! nop_sled {
+    () => {
+        concat!(nop16!(), nop16!(), nop16!(), nop16!())
+    };
+}
+macro_rules! nop16 {
+    () => {
+        "nop\nnop\nnop\nnop\nnop\nnop\nnop\nnop\nnop\nnop\nnop\nnop\nnop\nnop\nnop\nnop\n"
+    };
+}
+const _: () = assert!(MAX_VARIABLE_CYCLES == 64);
+
+/// Blocks the program for `cycles` CPU cycles (at most [`MAX_VARIABLE_CYCLES`]) plus a constant
+/// overhead, in single-cycle steps. Unlike [`delay_ns`], `cycles` can be a runtime value.
+#[inline(always)]
+pub fn delay_cycles(cycles: u32) {
+    let skip = MAX_VARIABLE_CYCLES - cycles.min(MAX_VARIABLE_CYCLES);
+
+    // don't let the compiler reorder the delay
+    compiler_fence(core::sync::atomic::Ordering::SeqCst);
+
+    // jump into a sled of nops, so that exactly `cycles` of them are executed
+    unsafe {
+        asm!(
+            "lsls {skip}, {skip}, #1", // 2 bytes per nop
+            "add pc, {skip}", // pc reads as the address of this instruction + 4
+            "nop", // padding, never executed
+            nop_sled!(),
+            skip = inout(reg) skip => _,
+            options(nomem, nostack),
+        )
+    };
+
+    // don't let the compiler reorder the delay
+    compiler_fence(core::sync::atomic::Ordering::SeqCst);
+}
+
+/// Blocks the program for 3 * `loop_count` CPU cycles.
+#[inline(always)]
+pub fn delay_loop_3cyc(loop_count: u32) {
+    // Cortex-M0+: 1 cycle to set the register for the loop count, then
+    // 3 cycles per iteration if loop continues, 2 if it breaks.
+    //
+    // → exactly 3 cycles per iteration
+    unsafe {
+        asm!(
+            // Use local labels to avoid R_ARM_THM_JUMP8 relocations which fail on thumbv6m.
+            "1:",
+            "subs {}, #1", // 1 cycle
+            "bne 1b", // 2 cycles if loop continues, 1 if not
+            inout(reg) loop_count => _,
+            options(nomem, nostack),
+        )
+    };
+}
+
+/// Blocks the program for one CPU cycles.
+#[inline(always)]
+fn nop() {
+    unsafe { asm!("nop", options(nomem, nostack)) };
+}
+
+/// A delay that took a different number of cycles than computed, see [`self_test`].
+#[derive(Clone, Copy)]
+pub struct SelfTestError {
+    pub expected: u32,
+    pub measured: u32,
+}
+
+/// Cycles that a measured delay may be off by, as the compiler can schedule some of the surrounding
+/// instructions into the measurement. XIP cache misses cost tens of cycles.
+const SELF_TEST_TOLERANCE: u32 = 4;
+
+/// Measures the delay functions with SysTick, which counts CPU cycles, and checks that they take
+/// the computed number of cycles at `SYSTEM_FREQ`, e.g. that they aren't stalled by flash XIP
+/// cache misses. Also checks that a pin write takes at least
+/// [`PIN_WRITE_CYCLES`], which the edge-to-edge delays rely on.
+///
+/// Runs from RAM, like the delay loops inlined into it.
+#[inline(never)]
+#[link_section = ".ram_text"]
+pub fn self_test<const SYSTEM_FREQ: u32>(syst: &mut SYST) -> Result<(), SelfTestError> {
+    /// Cycles that `$delay` takes, plus a constant overhead
+    macro_rules! measure {
+        ($delay:expr) => {{
+            compiler_fence(core::sync::atomic::Ordering::SeqCst);
+            let start = unsafe { (*SYST::PTR).cvr.read() };
+            $delay;
+            let end = unsafe { (*SYST::PTR).cvr.read() };
+            compiler_fence(core::sync::atomic::Ordering::SeqCst);
+
+            // SysTick counts down and wraps at 24 bits
+            start.wrapping_sub(end) & 0x00ff_ffff
+        }};
+    }
+
+    syst.set_clock_source(SystClkSource::Core);
+    syst.set_reload(0x00ff_ffff);
+    syst.clear_current();
+    syst.enable_counter();
+
+    let overhead = measure!({});
+    let check = |expected: u32, measured: u32| {
+        let measured = measured.wrapping_sub(overhead);
+        if measured.abs_diff(expected) <= SELF_TEST_TOLERANCE {
+            Ok(())
+        } else {
+            Err(SelfTestError { expected, measured })
+        }
+    };
+
+    // a pin write may be scheduled into the measurement, but can't take less than assumed
+    let check_at_least = |expected: u32, measured: u32| {
+        let measured = measured.wrapping_sub(overhead);
+        if (expected..=expected + SELF_TEST_TOLERANCE).contains(&measured) {
+            Ok(())
+        } else {
+            Err(SelfTestError { expected, measured })
+        }
+    };
+
+    check(3 * 100, measure!(delay_loop_3cyc(100)))?;
+    // up to the edge of a pin write, like the DRAM cycles, but without toggling any pin
+    check_at_least(
+        ns_to_cycles(100, SYSTEM_FREQ),
+        measure!({
+            delay_edge_to_edge(100, SYSTEM_FREQ, PIN_WRITE_CYCLES);
+            unsafe { (*pac::SIO::ptr()).gpio_out_xor.write(|w| w.bits(0)) };
+        }),
+    )?;
+    check(
+        ns_to_cycles(1000, SYSTEM_FREQ),
+        measure!(delay_ns::<1000, SYSTEM_FREQ>()),
+    )?;
+    // the sled has a constant overhead, only the difference is exact
+    let min = measure!(delay_cycles(black_box(0)));
+    let max = measure!(delay_cycles(black_box(MAX_VARIABLE_CYCLES)));
+    check(
+        MAX_VARIABLE_CYCLES,
+        max.wrapping_sub(min).wrapping_add(overhead),
+    )
+}
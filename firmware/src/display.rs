@@ -0,0 +1,180 @@
+//! The display panel, chosen with cargo features (exactly one of them):
+//!
+//! - `display-sh1106` (default): SH1106 over I²C
+//! - `display-ssd1306-i2c`: SSD1306 over I²C
+//! - `display-ssd1306-spi`: SSD1306 over SPI, D/C on GPIO28 (CS tied low)
+//!
+//! Add `display-128x32` for 128×32 panels, 128×64 otherwise. I²C panels are at address 0x3c, SDA on
+//! GPIO26 and SCL on GPIO27. SPI panels take SCK on GPIO26 and MOSI on GPIO27 instead.
+//!
+//! All of them are embedded-graphics `DrawTarget`s with a frame buffer, which the UI draws on and
+//! then shows with `flush()`. The panel is optional: if none answers at startup, the [`Screen`]
+//! draws on nothing and the tester runs headless.
+
+#[cfg(not(any(
+    feature = "display-sh1106",
+    feature = "display-ssd1306-i2c",
+    feature = "display-ssd1306-spi"
+)))]
+compile_error!("select a display with one of the `display-*` features");
+
+#[cfg(any(
+    all(feature = "display-sh1106", feature = "display-ssd1306-i2c"),
+    all(feature = "display-sh1106", feature = "display-ssd1306-spi"),
+    all(feature = "display-ssd1306-i2c", feature = "display-ssd1306-spi")
+))]
+compile_error!("only one of the `display-*` features can be selected, use `--no-default-features`");
+
+use embedded_graphics::{
+    pixelcolor::BinaryColor,
+    prelude::{DrawTarget, OriginDimensions, Size as GraphicsSize},
+    primitives::Rectangle,
+    Pixel,
+};
+
+use crate::hal::gpio::{
+    bank0::{Gpio26, Gpio27},
+    Pin,
+};
+
+pub const WIDTH: u32 = 128;
+#[cfg(not(feature = "display-128x32"))]
+pub const HEIGHT: u32 = 64;
+#[cfg(feature = "display-128x32")]
+pub const HEIGHT: u32 = 32;
+
+/// The bus the panel is connected to
+#[cfg(not(feature = "display-ssd1306-spi"))]
+pub type Bus = crate::hal::I2C<
+    crate::pac::I2C1,
+    (
+        Pin<Gpio26, crate::hal::gpio::FunctionI2C>,
+        Pin<Gpio27, crate::hal::gpio::FunctionI2C>,
+    ),
+>;
+#[cfg(feature = "display-ssd1306-spi")]
+pub type Bus = (
+    crate::hal::Spi<crate::hal::spi::Enabled, crate::pac::SPI1, 8>,
+    Pin<crate::hal::gpio::bank0::Gpio28, crate::hal::gpio::PushPullOutput>,
+);
+
+#[cfg(feature = "display-sh1106")]
+pub type Display = sh1106::mode::GraphicsMode<sh1106::interface::I2cInterface<Bus>>;
+#[cfg(feature = "display-ssd1306-i2c")]
+pub type Display = ssd1306::Ssd1306<
+    ssd1306::prelude::I2CInterface<Bus>,
+    Size,
+    ssd1306::mode::BufferedGraphicsMode<Size>,
+>;
+#[cfg(feature = "display-ssd1306-spi")]
+pub type Display = ssd1306::Ssd1306<
+    display_interface_spi::SPIInterfaceNoCS<
+        crate::hal::Spi<crate::hal::spi::Enabled, crate::pac::SPI1, 8>,
+        Pin<crate::hal::gpio::bank0::Gpio28, crate::hal::gpio::PushPullOutput>,
+    >,
+    Size,
+    ssd1306::mode::BufferedGraphicsMode<Size>,
+>;
+
+#[cfg(all(not(feature = "display-sh1106"), not(feature = "display-128x32")))]
+type Size = ssd1306::size::DisplaySize128x64;
+#[cfg(all(not(feature = "display-sh1106"), feature = "display-128x32"))]
+type Size = ssd1306::size::DisplaySize128x32;
+
+/// The panel, if one was found at startup. Drawing without one does nothing.
+pub struct Screen(Option<Display>);
+
+impl Screen {
+    /// Sets up the panel on `bus`, dimmed and cleared. Runs headless if it doesn't answer.
+    pub fn new(bus: Bus) -> Self {
+        Self(init(bus))
+    }
+
+    pub fn is_present(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub fn clear(&mut self) {
+        if let Some(display) = &mut self.0 {
+            display.clear();
+        }
+    }
+
+    /// Shows what was drawn.
+    pub fn flush(&mut self) {
+        if let Some(display) = &mut self.0 {
+            display.flush().unwrap();
+        }
+    }
+}
+
+impl OriginDimensions for Screen {
+    fn size(&self) -> GraphicsSize {
+        GraphicsSize::new(WIDTH, HEIGHT)
+    }
+}
+
+impl DrawTarget for Screen {
+    type Color = BinaryColor;
+    type Error = <Display as DrawTarget>::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<BinaryColor>>,
+    {
+        match &mut self.0 {
+            Some(display) => display.draw_iter(pixels),
+            None => Ok(()),
+        }
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: BinaryColor) -> Result<(), Self::Error> {
+        match &mut self.0 {
+            Some(display) => display.fill_solid(area, color),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Sets up the panel on `bus`, returns `None` if it doesn't answer.
+#[cfg(feature = "display-sh1106")]
+fn init(bus: Bus) -> Option<Display> {
+    #[cfg(not(feature = "display-128x32"))]
+    let size = sh1106::prelude::DisplaySize::Display128x64NoOffset;
+    #[cfg(feature = "display-128x32")]
+    let size = sh1106::prelude::DisplaySize::Display128x32;
+
+    let mut display: Display = sh1106::Builder::new()
+        .with_i2c_addr(0x3c)
+        .with_size(size)
+        .connect_i2c(bus)
+        .into();
+
+    // the first transfer, fails without a panel
+    display.init().ok()?;
+    display.set_contrast(20).unwrap();
+    display.clear();
+    display.flush().unwrap();
+    Some(display)
+}
+
+/// Sets up the panel on `bus`, returns `None` if it doesn't answer.
+#[cfg(not(feature = "display-sh1106"))]
+fn init(bus: Bus) -> Option<Display> {
+    use ssd1306::prelude::*;
+
+    #[cfg(feature = "display-ssd1306-i2c")]
+    let interface = ssd1306::I2CDisplayInterface::new(bus);
+    #[cfg(feature = "display-ssd1306-spi")]
+    let interface = display_interface_spi::SPIInterfaceNoCS::new(bus.0, bus.1);
+
+    let mut display = ssd1306::Ssd1306::new(interface, Size {}, DisplayRotation::Rotate0)
+        .into_buffered_graphics_mode();
+
+    // the first transfer, fails without a panel (never on SPI, which can't tell)
+    display.init().ok()?;
+    display.set_brightness(Brightness::DIMMEST).unwrap();
+    display.clear();
+    display.flush().unwrap();
+    Some(display)
+}
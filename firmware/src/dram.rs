@@ -0,0 +1,591 @@
+//! Driver for 4164/41256-style DRAMs (multiplexed address), and modules built from them.
+//!
+//! The cycle functions and the test loop are linked into RAM (`.ram_text`, see `memory.x`), so
+//! that flash XIP cache misses can't stretch the cycles.
+//!
+//! RAS may stay low for at most tRAS(max), about 10 µs, which is only enough for a few dozen page
+//! mode accesses. Longer bursts are split by closing and reopening the row (see
+//! [`DramTimingConfig::MAX_BURST`]). Interrupts are disabled while a row is open, so they can't
+//! stretch it, i.e. they're delayed by at most tRAS(max).
+//!
+//! Each time a row is closed, the next row in turn gets a RAS-only refresh cycle. The bursts are
+//! short enough that a row is refreshed at least every [`T_REFRESH`](crate::timings::T_REFRESH),
+//! as the chip requires (checked by [`DramTimingConfig::ASSERT_VALID`]). All rows take turns, also
+//! on chips with fewer refresh cycles than rows: refresh ignores the upper row address bits, so
+//! their refresh addresses still come around at the same pace.
+
+use core::{arch::asm, marker::PhantomData};
+
+use eh1_0_alpha::digital::{InputPin, OutputPin};
+
+use crate::{
+    chipdb::ChipType,
+    clocks::{self, with_clock, SysClock},
+    delay::{delay_cycles, MAX_VARIABLE_CYCLES},
+    delay_ns,
+    march::{self, Element},
+    memory::{AccessTimes, DetectError, MemoryUnderTest, TestError},
+    pac,
+    timings::{with_preset, DramTimingConfig, TimingPreset, ADDR_WRITE_CYCLES, PIN_WRITE_CYCLES},
+    Timings, ADDR_SETTLE, TRANSCEIVER_DELAY,
+};
+
+/// CPU cycles from lowering CAS until DOUT is sampled, besides [`delay_cycles`] (estimated from
+/// the disassembly, ±1 cycle)
+const SAMPLE_OVERHEAD_CYCLES: u32 = 4;
+/// How far the measured access times may be off (CPU cycles, either way): ±1 for
+/// [`SAMPLE_OVERHEAD_CYCLES`], and the data got valid at some point during the cycle before the
+/// first valid sample
+const SAMPLE_UNCERTAINTY_CYCLES: u32 = 2;
+/// CPU cycles from the end of the tRCD delay until CAS is low (setting the column address)
+const CAS_OVERHEAD_CYCLES: u32 = 3;
+/// RAS to CAS delay for measuring tCAC, longer than tRCD(max) of any supported chip so that the
+/// access is limited by CAS
+const CAS_LIMITED_RCD: u32 = 200;
+
+/// The data lines of a DRAM chip or module.
+pub trait DataLines {
+    /// Maximum number of data bits
+    const WIDTH: u8;
+    /// Supported sizes, as (number of address lines, name), from smallest to largest
+    const SIZES: &'static [(u8, &'static str)];
+
+    /// Sets the data to be written with the next (early) write cycle.
+    fn set(&mut self, word: u32);
+    /// Stops driving bidirectional data lines, needs to be done before a read cycle.
+    fn release(&mut self) {}
+    fn get(&mut self) -> u32;
+
+    /// Number of data bits that are tested, at most [`Self::WIDTH`]
+    fn width(&self) -> u8 {
+        Self::WIDTH
+    }
+
+    /// Position (as in U1, U2, …) of the chip holding data bit `bit`, for memory modules.
+    fn chip_position(&self, _bit: u8) -> Option<u8> {
+        None
+    }
+}
+
+/// Separate DIN and DOUT pins, as on single 4164/41256 chips.
+pub struct SingleBit<Din, Dout> {
+    din: Din,
+    dout: Dout,
+}
+
+impl<Din, Dout> SingleBit<Din, Dout> {
+    pub fn new(din: Din, dout: Dout) -> Self {
+        Self { din, dout }
+    }
+}
+
+impl<Din: OutputPin, Dout: InputPin> DataLines for SingleBit<Din, Dout> {
+    const WIDTH: u8 = 1;
+    const SIZES: &'static [(u8, &'static str)] = &[(8, "4164"), (9, "41256")];
+
+    #[inline(always)]
+    fn set(&mut self, word: u32) {
+        self.din.set_state((word & 1 != 0).into()).unwrap();
+    }
+
+    #[inline(always)]
+    fn get(&mut self) -> u32 {
+        self.dout.is_high().unwrap().into()
+    }
+}
+
+pub struct Dram41XX<We, Cas, Ras, Data> {
+    we: We,
+    cas: Cas,
+    ras: Ras,
+    data: Data,
+    addr: AddressBus,
+    num_addr_lines: u8,
+    num_data_bits: u8,
+    chip_type: ChipType,
+    preset: TimingPreset,
+    /// Whether interrupts were enabled before the current row was opened
+    interrupts_enabled: bool,
+    /// The open row
+    row: usize,
+    /// Page mode accesses left until the row has to be reopened, see [`Self::split_burst`]
+    burst_left: u32,
+    /// The row to refresh when the open one is closed
+    refresh_row: usize,
+}
+
+impl<We, Cas, Ras, Data> Dram41XX<We, Cas, Ras, Data>
+where
+    We: OutputPin,
+    Cas: OutputPin,
+    Ras: OutputPin,
+    Data: DataLines,
+{
+    pub fn new(sio: pac::SIO, we: We, cas: Cas, ras: Ras, data: Data) -> Self {
+        // fixed, so that dead chips of a module fail the test instead of shrinking it
+        let num_data_bits = data.width();
+        Self {
+            we,
+            cas,
+            ras,
+            data,
+            addr: AddressBus { sio, last_state: 0 },
+            num_addr_lines: Data::SIZES[0].0,
+            num_data_bits,
+            chip_type: ChipType::selected(crate::PART),
+            preset: TimingPreset::Default,
+            interrupts_enabled: false,
+            row: 0,
+            burst_left: 0,
+            refresh_row: 0,
+        }
+    }
+
+    /// Accesses the DRAM using the timings `T` instead of the [default ones](crate::Timings).
+    fn timed<T: DramTimingConfig>(&mut self) -> Timed<'_, We, Cas, Ras, Data, T> {
+        Timed {
+            dram: self,
+            timings: PhantomData,
+        }
+    }
+
+    /// The data bits of the layout, without data lines that it leaves unconnected (e.g. the parity
+    /// line of a module without parity), which could read back anything
+    fn data_mask(&self) -> u32 {
+        u32::MAX >> (32 - u32::from(self.num_data_bits))
+    }
+
+    /// Returns the data bits that can hold both a 0 and a 1. On modules, missing or completely
+    /// broken chips won't show up here.
+    fn working_bits(&mut self) -> u32 {
+        let mask = self.data_mask();
+
+        self.write_one_word_early::<Timings>(0, 0, 0);
+        let zeros = self.read_one_word::<Timings>(0, 0);
+        self.write_one_word_early::<Timings>(0, 0, mask);
+        let ones = self.read_one_word::<Timings>(0, 0);
+
+        !zeros & ones & mask
+    }
+
+    fn detect_size(&mut self, working_bits: u32) -> (u8, &'static str) {
+        let (&largest, smaller) = Data::SIZES.split_last().unwrap();
+
+        for &(num_addr_lines, name) in smaller {
+            let wrapped = (1 << num_addr_lines) + 8;
+            self.write_one_word_early::<Timings>(8, 8, 0);
+            self.write_one_word_early::<Timings>(wrapped, wrapped, working_bits);
+            if self.read_one_word::<Timings>(8, 8) & working_bits != 0 {
+                // wrapped around → this size
+                return (num_addr_lines, name);
+            }
+        }
+
+        // didn't wrap around → largest size
+        largest
+    }
+
+    /// Finds the first cycle after CAS falls at which DOUT holds the written data, for both a 0 and
+    /// a 1 in every working bit.
+    ///
+    /// RAS falls `rcd` ns before CAS (has to be a constant).
+    #[inline(always)]
+    fn first_valid_cycle<T: DramTimingConfig>(
+        &mut self,
+        working_bits: u32,
+        rcd: u32,
+    ) -> Option<u32> {
+        'cycles: for cycles in 0..=MAX_VARIABLE_CYCLES {
+            for word in [0, working_bits] {
+                // make DOUT hold the opposite value first, so that it's not mistaken for valid data
+                self.write_one_word_early::<T>(0, 0, !word & working_bits);
+                self.read_one_word::<T>(0, 0);
+
+                self.write_one_word_early::<T>(0, 0, word);
+                if self.read_sampled::<T>(rcd, cycles) & working_bits != word {
+                    continue 'cycles;
+                }
+            }
+
+            return Some(cycles);
+        }
+
+        None
+    }
+
+    /// Read cycle at row 0, col 0 that samples the data `cycles` cycles after CAS falls.
+    #[inline(always)]
+    fn read_sampled<T: DramTimingConfig>(&mut self, rcd: u32, cycles: u32) -> u32 {
+        self.data.release();
+        self.addr.set(0);
+        self.interrupts_enabled = disable_interrupts();
+        self.ras.set_low().unwrap();
+        T::delay(rcd);
+
+        self.addr.set(0);
+        self.cas.set_low().unwrap();
+        delay_cycles(cycles);
+        let word = self.data.get();
+
+        T::delay(T::T_CAS);
+        self.cas.set_high().unwrap();
+        T::delay(T::T_RAS_REST);
+        self.close_row::<T>();
+
+        word
+    }
+
+    #[link_section = ".ram_text"]
+    fn write_one_word_early<T: DramTimingConfig>(&mut self, row: usize, col: usize, word: u32) {
+        self.data.set(word);
+        self.we.set_low().unwrap();
+        self.open_row::<T>(row);
+        self.strobe_cas::<T>(col);
+
+        self.we.set_high().unwrap();
+        T::delay_edges(T::T_RAS_REST, PIN_WRITE_CYCLES);
+
+        self.close_row::<T>();
+    }
+
+    #[link_section = ".ram_text"]
+    fn read_one_word<T: DramTimingConfig>(&mut self, row: usize, col: usize) -> u32 {
+        // read cycle
+        self.data.release();
+        self.open_row::<T>(row);
+        self.strobe_cas::<T>(col);
+
+        // account for bus transceiver delay
+        T::delay(T::T_TRANSCEIVER);
+        let word = self.data.get();
+        T::delay_edges(T::T_RAS_REST, PIN_WRITE_CYCLES);
+
+        self.close_row::<T>();
+
+        word
+    }
+
+    #[link_section = ".ram_text"]
+    fn open_row<T: DramTimingConfig>(&mut self, row: usize) {
+        self.row = row;
+        self.burst_left = T::MAX_BURST;
+        self.addr.set(row);
+        self.interrupts_enabled = disable_interrupts();
+        self.ras.set_low().unwrap();
+        // until CAS falls after the column address is set
+        T::delay_edges(T::T_RCD, ADDR_WRITE_CYCLES + PIN_WRITE_CYCLES);
+    }
+
+    #[link_section = ".ram_text"]
+    fn close_row<T: DramTimingConfig>(&mut self) {
+        // every access ends here, so this checks all timings that are used
+        let () = T::ASSERT_VALID;
+
+        self.ras.set_high().unwrap();
+        T::delay_edges(T::T_RP, ADDR_WRITE_CYCLES + PIN_WRITE_CYCLES);
+
+        // RAS-only refresh cycle, before interrupts can delay it
+        self.addr.set(self.refresh_row);
+        self.ras.set_low().unwrap();
+        T::delay_edges(T::T_RAS, PIN_WRITE_CYCLES);
+        self.ras.set_high().unwrap();
+        self.refresh_row = (self.refresh_row + 1) & ((1 << self.num_addr_lines) - 1);
+
+        if self.interrupts_enabled {
+            enable_interrupts();
+        }
+        // until RAS falls after the next row address is set
+        T::delay_edges(T::T_RP, ADDR_WRITE_CYCLES + PIN_WRITE_CYCLES);
+    }
+
+    /// Closes and reopens the row if another page mode access would keep RAS low for longer than
+    /// tRAS(max).
+    #[inline(always)]
+    fn split_burst<T: DramTimingConfig>(&mut self) {
+        if self.burst_left == 0 {
+            self.close_row::<T>();
+            self.open_row::<T>(self.row);
+        }
+        self.burst_left -= 1;
+    }
+
+    #[link_section = ".ram_text"]
+    fn strobe_cas<T: DramTimingConfig>(&mut self, col: usize) {
+        self.addr.set(col);
+        self.cas.set_low().unwrap();
+        T::delay_edges(T::T_CAS, PIN_WRITE_CYCLES);
+        self.cas.set_high().unwrap();
+    }
+
+    #[link_section = ".ram_text"]
+    fn write_page_mode<T: DramTimingConfig>(&mut self, col: usize, word: u32) {
+        self.split_burst::<T>();
+        self.we.set_low().unwrap();
+        self.data.set(word);
+        self.strobe_cas::<T>(col);
+        self.we.set_high().unwrap();
+        // until CAS falls after the next column address is set
+        T::delay_edges(T::T_CP, ADDR_WRITE_CYCLES + PIN_WRITE_CYCLES);
+    }
+
+    #[link_section = ".ram_text"]
+    fn read_page_mode<T: DramTimingConfig>(&mut self, col: usize) -> u32 {
+        self.split_burst::<T>();
+        self.data.release();
+        self.strobe_cas::<T>(col);
+
+        // account for bus transceiver delay
+        T::delay(T::T_TRANSCEIVER);
+
+        let word = self.data.get();
+        // until CAS falls after the next column address is set
+        T::delay_edges(T::T_CP, ADDR_WRITE_CYCLES + PIN_WRITE_CYCLES);
+        word
+    }
+}
+
+impl<We, Cas, Ras, Data> MemoryUnderTest for Dram41XX<We, Cas, Ras, Data>
+where
+    We: OutputPin,
+    Cas: OutputPin,
+    Ras: OutputPin,
+    Data: DataLines,
+{
+    fn init(&mut self) {
+        self.we.set_high().unwrap();
+        self.cas.set_high().unwrap();
+        self.ras.set_high().unwrap();
+
+        delay_ns::<1000_0000>();
+
+        for _ in 0..8 {
+            self.ras.set_low().unwrap();
+            delay_ns::<1000>();
+            self.ras.set_high().unwrap();
+            delay_ns::<1000>();
+        }
+    }
+
+    fn detect(&mut self) -> Result<&'static str, DetectError> {
+        let working_bits = self.working_bits();
+        if working_bits == 0 {
+            return Err(DetectError::NoChip);
+        }
+
+        // e.g. a missing parity chip, the test reports it as failed
+        let dead_bits = !working_bits & self.data_mask();
+        for bit in 0..self.num_data_bits {
+            if dead_bits & (1 << bit) != 0 {
+                if let Some(position) = self.data.chip_position(bit) {
+                    defmt::warn!("no response from U{}", position);
+                }
+            }
+        }
+
+        let (num_addr_lines, name) = self.detect_size(working_bits);
+        self.num_addr_lines = num_addr_lines;
+
+        self.chip_type.check(num_addr_lines, name)
+    }
+
+    fn rows(&self) -> usize {
+        1 << self.num_addr_lines
+    }
+
+    fn cols(&self) -> usize {
+        1 << self.num_addr_lines
+    }
+
+    fn data_bits(&self) -> u8 {
+        self.num_data_bits
+    }
+
+    fn chip_position(&self, bit: u8) -> Option<u8> {
+        self.data.chip_position(bit)
+    }
+
+    // Accesses with the current clock and the selected preset, like `run_test`, which dispatches on
+    // them once per test instead of for every access.
+
+    #[link_section = ".ram_text"]
+    fn open_row(&mut self, row: usize) {
+        with_clock!(clocks::current(), C => {
+            with_preset!(self.preset, C, T => Dram41XX::open_row::<T>(self, row))
+        })
+    }
+
+    #[link_section = ".ram_text"]
+    fn close_row(&mut self) {
+        with_clock!(clocks::current(), C => {
+            with_preset!(self.preset, C, T => Dram41XX::close_row::<T>(self))
+        })
+    }
+
+    #[link_section = ".ram_text"]
+    fn write(&mut self, col: usize, word: u32) {
+        with_clock!(clocks::current(), C => {
+            with_preset!(self.preset, C, T => self.write_page_mode::<T>(col, word))
+        })
+    }
+
+    #[link_section = ".ram_text"]
+    fn read(&mut self, col: usize) -> u32 {
+        with_clock!(clocks::current(), C => {
+            with_preset!(self.preset, C, T => self.read_page_mode::<T>(col))
+        })
+    }
+
+    #[link_section = ".ram_text"]
+    fn run_test(&mut self, test: &[Element]) -> Result<(), TestError> {
+        with_clock!(clocks::current(), C => {
+            with_preset!(self.preset, C, T => march::run(&mut self.timed::<T>(), test))
+        })
+    }
+
+    fn set_chip_type(&mut self, chip_type: ChipType) {
+        self.chip_type = chip_type;
+    }
+
+    fn chip_type(&self) -> Option<ChipType> {
+        Some(self.chip_type)
+    }
+
+    fn set_timing_preset(&mut self, preset: TimingPreset) {
+        self.preset = preset;
+    }
+
+    fn timing_preset(&self) -> Option<TimingPreset> {
+        Some(self.preset)
+    }
+
+    #[link_section = ".ram_text"]
+    fn measure_access_times(&mut self) -> Option<AccessTimes> {
+        let working_bits = self.working_bits();
+
+        with_clock!(clocks::current(), C => {
+            with_preset!(self.preset, C, T => {
+                // with RAS falling long before CAS, the access is limited by tCAC, with the minimum
+                // tRCD, it's limited by tRAC
+                let cac_cycles = self.first_valid_cycle::<T>(working_bits, CAS_LIMITED_RCD)?;
+                let rac_cycles = self.first_valid_cycle::<T>(working_bits, T::DATASHEET.t_rcd)?;
+
+                // not with `NS_PER_CYCLE`, its rounding would add up
+                let ns = |cycles: u32| {
+                    (u64::from(cycles) * 1_000_000_000 / u64::from(C::FREQ)) as u32
+                };
+                let t_cac = ns(cac_cycles + SAMPLE_OVERHEAD_CYCLES);
+                let t_rac = T::DATASHEET.t_rcd
+                    + ns(CAS_OVERHEAD_CYCLES + rac_cycles + SAMPLE_OVERHEAD_CYCLES);
+
+                Some(AccessTimes {
+                    t_rac: t_rac.saturating_sub(TRANSCEIVER_DELAY),
+                    t_cac: t_cac.saturating_sub(TRANSCEIVER_DELAY),
+                    uncertainty: ns(SAMPLE_UNCERTAINTY_CYCLES),
+                })
+            })
+        })
+    }
+}
+
+/// A [`Dram41XX`] using the timings `T`, see [`Dram41XX::timed`].
+struct Timed<'a, We, Cas, Ras, Data, T> {
+    dram: &'a mut Dram41XX<We, Cas, Ras, Data>,
+    timings: PhantomData<T>,
+}
+
+impl<We, Cas, Ras, Data, T> MemoryUnderTest for Timed<'_, We, Cas, Ras, Data, T>
+where
+    We: OutputPin,
+    Cas: OutputPin,
+    Ras: OutputPin,
+    Data: DataLines,
+    T: DramTimingConfig,
+{
+    fn init(&mut self) {
+        self.dram.init();
+    }
+
+    fn detect(&mut self) -> Result<&'static str, DetectError> {
+        self.dram.detect()
+    }
+
+    fn rows(&self) -> usize {
+        self.dram.rows()
+    }
+
+    fn cols(&self) -> usize {
+        self.dram.cols()
+    }
+
+    fn data_bits(&self) -> u8 {
+        self.dram.data_bits()
+    }
+
+    fn chip_position(&self, bit: u8) -> Option<u8> {
+        self.dram.chip_position(bit)
+    }
+
+    #[link_section = ".ram_text"]
+    fn open_row(&mut self, row: usize) {
+        Dram41XX::open_row::<T>(self.dram, row);
+    }
+
+    #[link_section = ".ram_text"]
+    fn close_row(&mut self) {
+        Dram41XX::close_row::<T>(self.dram);
+    }
+
+    #[link_section = ".ram_text"]
+    fn write(&mut self, col: usize, word: u32) {
+        self.dram.write_page_mode::<T>(col, word);
+    }
+
+    #[link_section = ".ram_text"]
+    fn read(&mut self, col: usize) -> u32 {
+        self.dram.read_page_mode::<T>(col)
+    }
+}
+
+/// Disables interrupts and returns whether they were enabled before. Unlike `cortex_m::interrupt`,
+/// this doesn't call into flash.
+#[inline(always)]
+fn disable_interrupts() -> bool {
+    let primask: u32;
+    unsafe {
+        asm!(
+            "mrs {}, PRIMASK",
+            "cpsid i",
+            out(reg) primask,
+            options(nostack, preserves_flags),
+        )
+    };
+    primask & 1 == 0
+}
+
+/// Enables interrupts, see [`disable_interrupts`].
+#[inline(always)]
+fn enable_interrupts() {
+    unsafe { asm!("cpsie i", options(nostack, preserves_flags)) };
+}
+
+struct AddressBus {
+    sio: pac::SIO,
+    last_state: u32,
+}
+
+impl AddressBus {
+    #[inline(always)]
+    fn set(&mut self, addr: usize) {
+        let addr = addr as u32;
+        self.sio.gpio_out_xor.write(|f|
+                // this, in addition to the address pins being all low when AddressBus::last_state
+                // is initialized to 0, ensures that we only update/change the address pins. since
+                // enbedded-hal doesn't support setting multiple pins at once (and all address bits
+                // should be set at the same time), we have to do this by directly writing to the
+                // SIO registers instead.
+                unsafe { f.bits(addr ^ self.last_state) });
+        self.last_state = addr;
+
+        delay_ns::<ADDR_SETTLE>();
+    }
+}
@@ -0,0 +1,260 @@
+//! Status LED blink codes, so that the state of the tester can be told without reading the display.
+//!
+//! Each [`Code`] is a blink pattern and a colour. The [`Blinker`] shows it without blocking, as
+//! long as it's updated often enough (every few ms, e.g. from the UI loop). The patterns alone are
+//! distinct, so the onboard LED is enough. An external bicolour LED or WS2812 adds the colours, see
+//! [`crate::EXTERNAL_LED`].
+
+use eh1_0_alpha::digital::OutputPin;
+use pio::{Assembler, JmpCondition, OutDestination, SideSet};
+
+use crate::{
+    clocks::{self, ClockPreset},
+    hal::{
+        gpio::{
+            bank0::{Gpio17, Gpio18},
+            Pin, PushPullOutput,
+        },
+        pio::{
+            PIOBuilder, PinDir, Running, ShiftDirection, StateMachine, Tx, UninitStateMachine, PIO,
+            SM0,
+        },
+    },
+    pac::PIO1,
+};
+
+/// Length of one step of the patterns (ms)
+const STEP_MS: u32 = 125;
+
+/// What the tester is doing, as told by the LED
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Code {
+    /// No chip inserted, or no run started yet
+    Idle,
+    /// The inserted chip isn't the selected chip type
+    WrongChip,
+    /// Before the first result for the inserted chip
+    Testing,
+    Passed,
+    Failed,
+    /// The delay self-test failed, the tester can't be used
+    SelfTestFault,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Green,
+    Red,
+    Amber,
+}
+
+impl Code {
+    /// Pattern of 16 steps of [`STEP_MS`], lit where the bit is set (LSB first)
+    fn pattern(self) -> u16 {
+        match self {
+            // short flash every 2 s
+            Code::Idle => 0b1,
+            // double flash
+            Code::WrongChip => 0b101,
+            // slow blink
+            Code::Testing => 0x00ff,
+            Code::Passed => 0xffff,
+            // fast blink
+            Code::Failed => 0x5555,
+            // triple flash
+            Code::SelfTestFault => 0b10101,
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Code::Idle | Code::Testing => Color::Amber,
+            Code::Passed => Color::Green,
+            Code::WrongChip | Code::Failed | Code::SelfTestFault => Color::Red,
+        }
+    }
+}
+
+/// An LED that can show the [`Code`]s.
+pub trait StatusLed {
+    /// Lights the LED in `color` (as far as it can), or turns it off with `None`.
+    fn set(&mut self, color: Option<Color>);
+}
+
+/// A single-colour LED, e.g. the onboard one
+pub struct Single<P>(pub P);
+
+impl<P: OutputPin> StatusLed for Single<P> {
+    fn set(&mut self, color: Option<Color>) {
+        self.0.set_state(color.is_some().into()).unwrap();
+    }
+}
+
+/// Shows the same on both LEDs.
+impl<A: StatusLed, B: StatusLed> StatusLed for (A, B) {
+    fn set(&mut self, color: Option<Color>) {
+        self.0.set(color);
+        self.1.set(color);
+    }
+}
+
+/// The external status LED, see [`crate::EXTERNAL_LED`]
+pub enum External {
+    None,
+    /// Green on GPIO17, red on GPIO18 (both for amber), with common cathode
+    Bicolour(Pin<Gpio17, PushPullOutput>, Pin<Gpio18, PushPullOutput>),
+    /// Data in on GPIO17
+    Ws2812(Ws2812),
+}
+
+impl StatusLed for External {
+    fn set(&mut self, color: Option<Color>) {
+        match self {
+            External::None => {}
+            External::Bicolour(green, red) => {
+                let (g, r) = match color {
+                    None => (false, false),
+                    Some(Color::Green) => (true, false),
+                    Some(Color::Red) => (false, true),
+                    Some(Color::Amber) => (true, true),
+                };
+                green.set_state(g.into()).unwrap();
+                red.set_state(r.into()).unwrap();
+            }
+            External::Ws2812(ws2812) => ws2812.set(color),
+        }
+    }
+}
+
+/// Shows a [`Code`] on a [`StatusLed`].
+pub struct Blinker<L> {
+    led: L,
+    code: Code,
+    /// What the LED currently shows, to only touch it on changes
+    shown: Option<Option<Color>>,
+}
+
+impl<L: StatusLed> Blinker<L> {
+    pub fn new(led: L) -> Self {
+        Self {
+            led,
+            code: Code::Idle,
+            shown: None,
+        }
+    }
+
+    pub fn set_code(&mut self, code: Code) {
+        self.code = code;
+    }
+
+    pub fn code(&self) -> Code {
+        self.code
+    }
+
+    /// Updates the LED for the time `now_us` (µs, see [`crate::progress::now_us`]).
+    pub fn update(&mut self, now_us: u32) {
+        let step = now_us / 1000 / STEP_MS % 16;
+        let lit = self.code.pattern() & (1 << step) != 0;
+        let color = lit.then_some(self.code.color());
+        if self.shown != Some(color) {
+            self.led.set(color);
+            self.shown = Some(color);
+        }
+    }
+}
+
+type Ws2812Sm = (PIO1, SM0);
+
+/// WS2812 data pin
+const WS2812_PIN: u8 = 17;
+/// Bit rate of the WS2812 protocol (Hz)
+const WS2812_FREQ: u32 = 800_000;
+/// State machine cycles per bit, see [`ws2812_program`]
+const CYCLES_PER_BIT: u32 = 10;
+
+/// A WS2812 RGB LED, driven by a state machine of PIO1.
+pub struct Ws2812 {
+    _pio: PIO<PIO1>,
+    sm: StateMachine<Ws2812Sm, Running>,
+    tx: Tx<Ws2812Sm>,
+    /// Clock the state machine's divisor was computed for
+    clock: ClockPreset,
+}
+
+impl Ws2812 {
+    pub fn new(mut pio: PIO<PIO1>, sm: UninitStateMachine<Ws2812Sm>) -> Self {
+        let installed = pio.install(&ws2812_program()).unwrap();
+        let clock = clocks::current();
+        let (int, frac) = ws2812_divisor(clock);
+
+        let (mut sm, _, tx) = PIOBuilder::from_program(installed)
+            .side_set_pin_base(WS2812_PIN)
+            .out_shift_direction(ShiftDirection::Left)
+            .autopull(true)
+            .pull_threshold(24)
+            .clock_divisor_fixed_point(int, frac)
+            .build(sm);
+        sm.set_pindirs([(WS2812_PIN, PinDir::Output)]);
+
+        Self {
+            _pio: pio,
+            sm: sm.start(),
+            tx,
+            clock,
+        }
+    }
+
+    fn set(&mut self, color: Option<Color>) {
+        // the system clock can be switched at runtime
+        if self.clock != clocks::current() {
+            self.clock = clocks::current();
+            let (int, frac) = ws2812_divisor(self.clock);
+            self.sm.clock_divisor_fixed_point(int, frac);
+        }
+
+        // dimmed, at full brightness it's blinding
+        let (r, g, b): (u32, u32, u32) = match color {
+            None => (0, 0, 0),
+            Some(Color::Green) => (0, 32, 0),
+            Some(Color::Red) => (32, 0, 0),
+            Some(Color::Amber) => (32, 12, 0),
+        };
+        // GRB, MSB first, in the upper 24 bits
+        let grb = (g << 24) | (r << 16) | (b << 8);
+        // only sent on changes, so the FIFO never stays full for long
+        while !self.tx.write(grb) {}
+    }
+}
+
+/// Divisor of the system clock for [`CYCLES_PER_BIT`] state machine cycles per bit (16.8 fixed
+/// point)
+fn ws2812_divisor(clock: ClockPreset) -> (u16, u8) {
+    let divisor = u64::from(clock.freq()) * 256 / u64::from(WS2812_FREQ * CYCLES_PER_BIT);
+    ((divisor >> 8) as u16, divisor as u8)
+}
+
+/// Sends the 24 bits of each word as a short (0) or long (1) high pulse, 10 cycles per bit.
+fn ws2812_program() -> pio::Program<{ pio::RP2040_MAX_PROGRAM_SIZE }> {
+    // high for T1 (0) or T1 + T2 (1) cycles, then low for the rest
+    const T1: u8 = 2;
+    const T2: u8 = 5;
+    const T3: u8 = 3;
+    const _: () = assert!((T1 + T2 + T3) as u32 == CYCLES_PER_BIT);
+
+    let mut a = Assembler::<{ pio::RP2040_MAX_PROGRAM_SIZE }>::new_with_side_set(SideSet::new(
+        false, 1, false,
+    ));
+    let mut wrap_target = a.label();
+    let mut wrap_source = a.label();
+    let mut zero = a.label();
+
+    a.bind(&mut wrap_target);
+    a.out_with_delay_and_side_set(OutDestination::X, 1, T3 - 1, 0);
+    a.jmp_with_delay_and_side_set(JmpCondition::XIsZero, &mut zero, T1 - 1, 1);
+    a.jmp_with_delay_and_side_set(JmpCondition::Always, &mut wrap_target, T2 - 1, 1);
+    a.bind(&mut zero);
+    a.nop_with_delay_and_side_set(T2 - 1, 0);
+    a.bind(&mut wrap_source);
+
+    a.assemble_with_wrap(wrap_source, wrap_target)
+}
@@ -0,0 +1,824 @@
+#![no_std]
+#![no_main]
+
+// Provide an alias for our BSP so we can switch targets quickly.
+// Uncomment the BSP you included in Cargo.toml, the rest of the code does not need to change.
+use defmt::*;
+use defmt_rtt as _;
+use panic_probe as _;
+use rp_pico as board;
+
+use board::{
+    entry,
+    hal::{self, pac, prelude::*},
+};
+use clocks::{ClockPreset, SysClock};
+use core::ptr::addr_of_mut;
+use dram::{Dram41XX, SingleBit};
+use eh1_0_alpha::digital::{InputPin, OutputPin};
+use embedded_graphics::{
+    mono_font::{self, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::Point,
+    text::{Baseline, Text},
+    Drawable,
+};
+use fugit::RateExtU32;
+use hal::{
+    dma::DMAExt,
+    gpio::{
+        bank0::{Gpio17, Gpio18, Gpio19},
+        FunctionPio0, FunctionPio1, Pin, PinId, PinState,
+    },
+    multicore::{self, Multicore, Stack},
+    pio::PIOExt,
+};
+use memory::{AccessTimes, MemoryUnderTest, TestError, MAX_DATA_BITS};
+use picoram_core::{chipdb, march, memory, protocol, speedgrade};
+use pio_dram::PioDram;
+use simm::{SimmData, SimmLayout};
+use speedgrade::SpeedGrade;
+use sram::Sram62XX;
+use ufmt::uwrite;
+use ui::{Command, Event, Failure, Setting, Settings, Ui};
+use usb_device::class_prelude::UsbBusAllocator;
+
+mod buzzer;
+mod clocks;
+mod console;
+mod delay;
+mod display;
+mod dram;
+mod led;
+mod pio_dram;
+mod progress;
+mod simm;
+mod sram;
+mod timings;
+mod ui;
+
+/// System clock at startup, can be switched at runtime with a button
+const CLOCK: ClockPreset = ClockPreset::Mhz125;
+
+/// The DRAM part to test, e.g. `Some(chipdb::select(&chipdb::TMS4164, "-15"))`. Its datasheet
+/// timings are used, and chips of a different size are rejected.
+///
+/// `None` autodetects 4164 vs 41256 and uses the default timings below.
+const PART: Option<chipdb::Selection> = None;
+
+/// Default timings, used if no [`PART`] is selected
+type Timings<C = clocks::Fastest> = timings::PartOr<timings::Dram150Ns<C>>;
+type SramTimings = timings::Sram150Ns;
+
+/// The adapter board plugged into the tester, decides which kind of memory is tested.
+#[allow(dead_code)]
+enum Adapter {
+    /// 4164/41256 DRAMs
+    Dram41XX,
+    /// 6116/6264/62256 SRAMs
+    Sram62XX,
+    /// 30-pin 256K/1M SIMMs. The data lines take GPIO17-22, so there are no menu buttons, no
+    /// [`EXTERNAL_LED`] and no [`BUZZER`], the tester is driven from the [`console`] instead.
+    Simm30,
+}
+
+const ADAPTER: Adapter = Adapter::Dram41XX;
+
+/// An LED for the status blink codes besides the onboard one, adds colours (see [`led`]). Not
+/// available with [`Adapter::Simm30`], which takes all GPIOs.
+#[allow(dead_code)]
+enum ExternalLed {
+    None,
+    /// Green on GPIO17, red on GPIO18
+    Bicolour,
+    /// WS2812 data in on GPIO17
+    Ws2812,
+}
+
+const EXTERNAL_LED: ExternalLed = ExternalLed::None;
+
+/// Piezo buzzer on GPIO19 (through a transistor if it draws more than a few mA), see [`buzzer`].
+/// Not available with [`Adapter::Simm30`].
+const BUZZER: bool = false;
+/// Generate the DRAM cycles with a PIO state machine instead of bit-banging them, only used with
+/// [`Adapter::Dram41XX`], see [`pio_dram`]
+const DRAM_PIO: bool = false;
+
+#[allow(dead_code)]
+enum Mode {
+    /// Runs the full test of the adapter over and over
+    Test,
+    /// Finds the fastest timing preset the chip passes with, see [`speedgrade`]
+    SpeedGrade,
+    /// Measures tRAC and tCAC of the chip
+    AccessTimes,
+}
+
+const MODE: Mode = Mode::Test;
+/// Only used with [`Adapter::Simm30`], use a layout without parity for modules without a parity
+/// chip, the parity bit of the others is tested and fails if its chip is dead
+const SIMM_LAYOUT: SimmLayout = SimmLayout::NineChip;
+
+/// Propagation delay of the bus transceivers (ns)
+const TRANSCEIVER_DELAY: u32 = 14;
+/// Propagation delay of the 74HCT595s holding the SRAM address, from RCLK to the outputs (ns)
+const SHIFT_REGISTER_DELAY: u32 = 50;
+/// Add if longer leads, ringing, etc.
+const ADDR_SETTLE: u32 = 0;
+
+/// Computed for the [fastest clock](clocks::Fastest), so it's never too short.
+#[inline(always)]
+fn delay_ns<const NS: u32>() {
+    delay::delay_ns::<NS, { <clocks::Fastest as SysClock>::FREQ }>();
+}
+
+// the SIMM data lines are on the pins of the external LED and the buzzer
+const _: () = assert!(
+    !(matches!(ADAPTER, Adapter::Simm30) && (BUZZER || !matches!(EXTERNAL_LED, ExternalLed::None))),
+    "the external LED and the buzzer can't be used with the SIMM adapter"
+);
+
+// D/C of SPI panels is on GPIO28, which SIMMs need for data
+const _: () = assert!(
+    !(cfg!(feature = "display-ssd1306-spi") && matches!(ADAPTER, Adapter::Simm30)),
+    "SPI displays can't be used with the SIMM adapter"
+);
+
+#[entry]
+fn main() -> ! {
+    let mut pac = pac::Peripherals::take().unwrap();
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    let mut clocks = clocks::init_clocks_and_plls(
+        CLOCK,
+        // External high-speed crystal on the pico board is 12Mhz
+        12_000_000u32,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    // takes the timer out of reset, for measuring the duration of passes (see `progress.rs`)
+    let _timer = hal::Timer::new(pac.TIMER, &mut pac.RESETS);
+    // SAFETY: the timer's registers are always there
+    unsafe { progress::set_clock((*pac::TIMER::ptr()).timerawl.as_ptr()) };
+
+    let core = pac::CorePeripherals::take().unwrap();
+    let mut delay =
+        cortex_m::delay::Delay::new(core.SYST, clocks.manager.system_clock.freq().to_Hz());
+
+    // let voltage rails settle before initializing
+    delay.delay_ms(500);
+
+    let mut sio = hal::Sio::new(pac.SIO);
+    let pins = board::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let led = pins.led.into_push_pull_output();
+
+    #[cfg(not(feature = "display-ssd1306-spi"))]
+    let bus = {
+        let i2c_scl = pins.gpio27.into_mode::<hal::gpio::FunctionI2C>();
+        let i2c_sda = pins.gpio26.into_mode::<hal::gpio::FunctionI2C>();
+        hal::I2C::new_controller(
+            pac.I2C1,
+            i2c_sda,
+            i2c_scl,
+            400u32.kHz(),
+            &mut pac.RESETS,
+            clocks.manager.peripheral_clock.freq(),
+        )
+    };
+    #[cfg(feature = "display-ssd1306-spi")]
+    let bus = {
+        pins.gpio26.into_mode::<hal::gpio::FunctionSpi>();
+        pins.gpio27.into_mode::<hal::gpio::FunctionSpi>();
+        let dc = pins.gpio28.into_push_pull_output();
+        let spi = hal::Spi::<_, _, 8>::new(pac.SPI1).init(
+            &mut pac.RESETS,
+            clocks.manager.peripheral_clock.freq(),
+            8u32.MHz(),
+            &embedded_hal::spi::MODE_0,
+        );
+        (spi, dc)
+    };
+
+    let mut display = display::Screen::new(bus);
+    if !display.is_present() {
+        warn!("no display found, running headless with LED blink codes");
+    }
+
+    let char_style = MonoTextStyle::new(&mono_font::ascii::FONT_7X13, BinaryColor::On);
+
+    // make sure the delays take as long as computed before testing with them
+    if let Err(err) = clocks.self_test(delay.free()) {
+        error!(
+            "delay self-test failed: {} cycles instead of {}",
+            err.measured, err.expected
+        );
+
+        let mut s = heapless::String::<64>::new();
+        uwrite!(
+            s,
+            "Delay self-test\nfailed: {} cycles\ninstead of {}",
+            err.measured,
+            err.expected
+        )
+        .unwrap();
+        Text::with_baseline(&s, Point::zero(), char_style, Baseline::Top)
+            .draw(&mut display)
+            .unwrap();
+        display.flush();
+
+        // refuse to test with wrong timings
+        let mut blinker = led::Blinker::new(led::Single(led));
+        blinker.set_code(led::Code::SelfTestFault);
+        loop {
+            blinker.update(progress::now_us());
+        }
+    }
+
+    // the serial console, serviced by the UI
+    let usb_bus = cortex_m::singleton!(: UsbBusAllocator<hal::usb::UsbBus> =
+        UsbBusAllocator::new(hal::usb::UsbBus::new(
+            pac.USBCTRL_REGS,
+            pac.USBCTRL_DPRAM,
+            clocks.take_usb_clock().unwrap(),
+            true,
+            &mut pac.RESETS,
+        ))
+    )
+    .unwrap();
+    let console = console::Console::new(usb_bus);
+
+    let pac2 = unsafe { pac::Peripherals::steal() };
+
+    // the UI runs on core1, so that the tests never wait for it
+    let mut mc = Multicore::new(&mut pac.PSM, &mut pac.PPB, &mut sio.fifo);
+    let cores = mc.cores();
+    let core1 = &mut cores[1];
+
+    // TXS0108E output enable pin, start disabled
+    let mut txs_oe = pins.gpio16.into_push_pull_output_in_state(PinState::Low);
+
+    match ADAPTER {
+        Adapter::Dram41XX if DRAM_PIO => {
+            // address, ~WRT, ~CAS, ~RAS and DIN pins, driven by the state machine
+            pins.gpio0.into_mode::<FunctionPio0>();
+            pins.gpio1.into_mode::<FunctionPio0>();
+            pins.gpio2.into_mode::<FunctionPio0>();
+            pins.gpio3.into_mode::<FunctionPio0>();
+            pins.gpio4.into_mode::<FunctionPio0>();
+            pins.gpio5.into_mode::<FunctionPio0>();
+            pins.gpio6.into_mode::<FunctionPio0>();
+            pins.gpio7.into_mode::<FunctionPio0>();
+            pins.gpio8.into_mode::<FunctionPio0>();
+            pins.gpio11.into_mode::<FunctionPio0>();
+            pins.gpio12.into_mode::<FunctionPio0>();
+            pins.gpio13.into_mode::<FunctionPio0>();
+            pins.gpio14.into_mode::<FunctionPio0>();
+
+            // DOUT pin (out of DRAM) (floating as recommended by TXS0108E datasheet)
+            pins.gpio15.into_floating_input();
+
+            // menu buttons to GND: select entry, change value, start/stop
+            let select_button = pins.gpio22.into_pull_up_input();
+            let change_button = pins.gpio21.into_pull_up_input();
+            let start_button = pins.gpio20.into_pull_up_input();
+            let external = external_led(pins.gpio17, pins.gpio18, pac.PIO1, &mut pac.RESETS);
+            let buzzer = buzzer(pins.gpio19, pac.PWM, &mut pac.RESETS);
+
+            let (pio, sm0, _, _, _) = pac.PIO0.split(&mut pac.RESETS);
+            let dma = pac.DMA.split(&mut pac.RESETS);
+
+            txs_oe.set_high().unwrap();
+            let dram = PioDram::new(pio, sm0, dma.ch0, dma.ch1);
+
+            let link = spawn_ui(
+                core1,
+                display,
+                (led::Single(led), external),
+                buzzer,
+                console,
+                char_style,
+                move || select_button.is_low().unwrap(),
+                move || change_button.is_low().unwrap(),
+                move || start_button.is_low().unwrap(),
+            );
+            run(dram, march::Test::MovingInversions, &mut clocks, link)
+        }
+        Adapter::Dram41XX => {
+            // address pins
+            pins.gpio0.into_push_pull_output_in_state(PinState::Low);
+            pins.gpio1.into_push_pull_output_in_state(PinState::Low);
+            pins.gpio2.into_push_pull_output_in_state(PinState::Low);
+            pins.gpio3.into_push_pull_output_in_state(PinState::Low);
+            pins.gpio4.into_push_pull_output_in_state(PinState::Low);
+            pins.gpio5.into_push_pull_output_in_state(PinState::Low);
+            pins.gpio6.into_push_pull_output_in_state(PinState::Low);
+            pins.gpio7.into_push_pull_output_in_state(PinState::Low);
+            pins.gpio8.into_push_pull_output_in_state(PinState::Low);
+
+            // ~WRT pin
+            let we = pins.gpio11.into_push_pull_output();
+            // ~CAS pin
+            let cas = pins.gpio12.into_push_pull_output();
+            // ~RAS pin
+            let ras = pins.gpio13.into_push_pull_output();
+
+            // DIN pin (into DRAM)
+            let din = pins.gpio14.into_push_pull_output();
+
+            // DOUT pin (out of DRAM) (floating as recommended by TXS0108E datasheet)
+            let dout = pins.gpio15.into_floating_input();
+
+            if let Some(part) = PART {
+                info!(
+                    "testing {}{}, {} refresh cycles in {} ms",
+                    part.part.name,
+                    part.grade.suffix,
+                    part.part.refresh_cycles,
+                    part.part.refresh_ms
+                );
+            }
+
+            // menu buttons to GND: select entry, change value, start/stop
+            let select_button = pins.gpio22.into_pull_up_input();
+            let change_button = pins.gpio21.into_pull_up_input();
+            let start_button = pins.gpio20.into_pull_up_input();
+            let external = external_led(pins.gpio17, pins.gpio18, pac.PIO1, &mut pac.RESETS);
+            let buzzer = buzzer(pins.gpio19, pac.PWM, &mut pac.RESETS);
+
+            txs_oe.set_high().unwrap();
+            let dram = Dram41XX::new(pac2.SIO, we, cas, ras, SingleBit::new(din, dout));
+
+            let link = spawn_ui(
+                core1,
+                display,
+                (led::Single(led), external),
+                buzzer,
+                console,
+                char_style,
+                move || select_button.is_low().unwrap(),
+                move || change_button.is_low().unwrap(),
+                move || start_button.is_low().unwrap(),
+            );
+            run(dram, march::Test::MovingInversions, &mut clocks, link)
+        }
+        Adapter::Sram62XX => {
+            // data pins, only driven while writing (floating as recommended by TXS0108E
+            // datasheet)
+            pins.gpio0.into_floating_input();
+            pins.gpio1.into_floating_input();
+            pins.gpio2.into_floating_input();
+            pins.gpio3.into_floating_input();
+            pins.gpio4.into_floating_input();
+            pins.gpio5.into_floating_input();
+            pins.gpio6.into_floating_input();
+            pins.gpio7.into_floating_input();
+
+            // 74HCT595 SER, SRCLK and RCLK pins
+            let ser = pins.gpio8.into_push_pull_output_in_state(PinState::Low);
+            let srclk = pins.gpio9.into_push_pull_output_in_state(PinState::Low);
+            let rclk = pins.gpio10.into_push_pull_output_in_state(PinState::Low);
+
+            // ~WE pin
+            let we = pins.gpio11.into_push_pull_output_in_state(PinState::High);
+            // ~OE pin
+            let oe = pins.gpio12.into_push_pull_output_in_state(PinState::High);
+            // ~CE pin
+            let ce = pins.gpio13.into_push_pull_output_in_state(PinState::High);
+
+            // menu buttons to GND: select entry, change value, start/stop
+            let select_button = pins.gpio22.into_pull_up_input();
+            let change_button = pins.gpio21.into_pull_up_input();
+            let start_button = pins.gpio20.into_pull_up_input();
+            let external = external_led(pins.gpio17, pins.gpio18, pac.PIO1, &mut pac.RESETS);
+            let buzzer = buzzer(pins.gpio19, pac.PWM, &mut pac.RESETS);
+
+            txs_oe.set_high().unwrap();
+            let sram = Sram62XX::new(pac2.SIO, ce, oe, we, ser, srclk, rclk);
+
+            let link = spawn_ui(
+                core1,
+                display,
+                (led::Single(led), external),
+                buzzer,
+                console,
+                char_style,
+                move || select_button.is_low().unwrap(),
+                move || change_button.is_low().unwrap(),
+                move || start_button.is_low().unwrap(),
+            );
+            run(sram, march::Test::MarchCMinus, &mut clocks, link)
+        }
+        Adapter::Simm30 => {
+            // address pins
+            pins.gpio0.into_push_pull_output_in_state(PinState::Low);
+            pins.gpio1.into_push_pull_output_in_state(PinState::Low);
+            pins.gpio2.into_push_pull_output_in_state(PinState::Low);
+            pins.gpio3.into_push_pull_output_in_state(PinState::Low);
+            pins.gpio4.into_push_pull_output_in_state(PinState::Low);
+            pins.gpio5.into_push_pull_output_in_state(PinState::Low);
+            pins.gpio6.into_push_pull_output_in_state(PinState::Low);
+            pins.gpio7.into_push_pull_output_in_state(PinState::Low);
+            pins.gpio8.into_push_pull_output_in_state(PinState::Low);
+            pins.gpio9.into_push_pull_output_in_state(PinState::Low);
+
+            // ~WRT pin
+            let we = pins.gpio11.into_push_pull_output();
+            // ~CAS pin
+            let cas = pins.gpio12.into_push_pull_output();
+            // ~RAS pin
+            let ras = pins.gpio13.into_push_pull_output();
+
+            // PD pin (into parity chip)
+            let pd = pins.gpio14.into_push_pull_output();
+            // PQ pin (out of parity chip) (floating as recommended by TXS0108E datasheet)
+            let pq = pins.gpio15.into_floating_input();
+
+            // DQ0-DQ7 pins, only driven while writing (see `simm::DQ_PINS`)
+            pins.gpio17.into_floating_input();
+            pins.gpio18.into_floating_input();
+            pins.gpio19.into_floating_input();
+            pins.gpio20.into_floating_input();
+            pins.gpio21.into_floating_input();
+            pins.gpio22.into_floating_input();
+            pins.gpio10.into_floating_input();
+            // taken by SPI displays, ruled out above
+            #[cfg(not(feature = "display-ssd1306-spi"))]
+            pins.gpio28.into_floating_input();
+
+            txs_oe.set_high().unwrap();
+            let data_sio = unsafe { pac::Peripherals::steal() }.SIO;
+            let data = SimmData::new(data_sio, pd, pq, SIMM_LAYOUT);
+            let simm = Dram41XX::new(pac2.SIO, we, cas, ras, data);
+
+            // all GPIOs are taken, so there are no buttons for the menu and no external LED
+            warn!("SIMM adapter: no menu buttons, LED or buzzer, use the serial console");
+            let external = led::External::None;
+            let buzzer = buzzer::Buzzer::NONE;
+            let link = spawn_ui(
+                core1,
+                display,
+                (led::Single(led), external),
+                buzzer,
+                console,
+                char_style,
+                || false,
+                || false,
+                || false,
+            );
+            run(simm, march::Test::MovingInversions, &mut clocks, link)
+        }
+    }
+}
+
+/// Sets up the [`EXTERNAL_LED`].
+fn external_led(
+    gpio17: Pin<Gpio17, <Gpio17 as PinId>::Reset>,
+    gpio18: Pin<Gpio18, <Gpio18 as PinId>::Reset>,
+    pio1: pac::PIO1,
+    resets: &mut pac::RESETS,
+) -> led::External {
+    match EXTERNAL_LED {
+        ExternalLed::None => led::External::None,
+        ExternalLed::Bicolour => led::External::Bicolour(
+            gpio17.into_push_pull_output_in_state(PinState::Low),
+            gpio18.into_push_pull_output_in_state(PinState::Low),
+        ),
+        ExternalLed::Ws2812 => {
+            gpio17.into_mode::<FunctionPio1>();
+            let (pio, sm0, _, _, _) = pio1.split(resets);
+            led::External::Ws2812(led::Ws2812::new(pio, sm0))
+        }
+    }
+}
+
+/// Sets up the [`BUZZER`].
+fn buzzer(
+    gpio19: Pin<Gpio19, <Gpio19 as PinId>::Reset>,
+    pwm: pac::PWM,
+    resets: &mut pac::RESETS,
+) -> buzzer::Buzzer {
+    if !BUZZER {
+        return buzzer::Buzzer::NONE;
+    }
+    let slices = hal::pwm::Slices::new(pwm, resets);
+    buzzer::Buzzer::new(slices.pwm1, gpio19)
+}
+
+/// Starts the UI on core1, returns the test engine's link to it.
+#[allow(clippy::too_many_arguments)]
+fn spawn_ui<Led, SelectButton, ChangeButton, StartButton>(
+    core1: &mut multicore::Core,
+    display: display::Screen,
+    led: Led,
+    buzzer: buzzer::Buzzer,
+    console: console::Console,
+    char_style: MonoTextStyle<'static, BinaryColor>,
+    select_button_pressed: SelectButton,
+    change_button_pressed: ChangeButton,
+    start_button_pressed: StartButton,
+) -> ui::Link
+where
+    Led: led::StatusLed + Send + 'static,
+    SelectButton: FnMut() -> bool + Send + 'static,
+    ChangeButton: FnMut() -> bool + Send + 'static,
+    StartButton: FnMut() -> bool + Send + 'static,
+{
+    static mut CORE1_STACK: Stack<4096> = Stack::new();
+
+    let (ui, link) = Ui::new(
+        display,
+        led,
+        buzzer,
+        console,
+        char_style,
+        select_button_pressed,
+        change_button_pressed,
+        start_button_pressed,
+    );
+    core1
+        .spawn(unsafe { &mut *addr_of_mut!(CORE1_STACK.mem) }, move || {
+            ui.run()
+        })
+        .unwrap();
+    link
+}
+
+/// Choices for the number of passes per run, `None` runs until stopped
+const LOOPS: [Option<u32>; 4] = [None, Some(1), Some(10), Some(100)];
+/// How often the chip is detected while there's none, or while stopped (µs)
+const DETECT_INTERVAL_US: u32 = 250_000;
+
+/// Runs `test` on `mem` forever, restarting whenever the chip is changed or removed. Reports to the
+/// UI through `link`.
+///
+/// Applies the settings changed from the menu or the console between two passes, and only tests
+/// while a run is started. A run stops by itself after the selected number of passes.
+fn run<M: MemoryUnderTest>(
+    mut mem: M,
+    test: march::Test,
+    sys_clocks: &mut clocks::Clocks,
+    mut link: ui::Link,
+) -> ! {
+    let mut settings = Settings {
+        test,
+        chip_type: mem.chip_type(),
+        preset: mem.timing_preset(),
+        clock: clocks::current(),
+        loops: LOOPS[0],
+        running: true,
+    };
+    // passes of the current run
+    let mut passes = 0u32;
+
+    'outer: loop {
+        let mut last_detected = None;
+        let chip = loop {
+            // e.g. to select the chip type of the inserted chip
+            if apply_commands(&mut mem, &mut settings, sys_clocks, &mut link) {
+                passes = 0;
+            }
+
+            mem.init();
+
+            let detected = mem.detect();
+            if last_detected != Some(detected) {
+                link.send(Event::Chip(detected));
+                last_detected = Some(detected);
+            }
+            if let Ok(chip) = detected {
+                break chip;
+            }
+            link.wait_for_command(DETECT_INTERVAL_US);
+        };
+
+        link.send(Event::Settings(settings));
+        let mut pass_count = 0u32;
+
+        loop {
+            if mem.detect() != Ok(chip) {
+                // chip changed, or removed, restart
+                continue 'outer;
+            }
+
+            if apply_commands(&mut mem, &mut settings, sys_clocks, &mut link) {
+                pass_count = 0;
+                passes = 0;
+            }
+            if !settings.running {
+                // only to notice the chip being changed
+                link.wait_for_command(DETECT_INTERVAL_US);
+                continue;
+            }
+
+            match MODE {
+                Mode::SpeedGrade => {
+                    let grade = speedgrade::grade(&mut mem);
+                    match grade {
+                        SpeedGrade::Passed(preset) => {
+                            let grade = preset.speed_grade().unwrap_or("?");
+                            info!("speed grade: {} (passes @{})", grade, preset.name());
+                        }
+                        SpeedGrade::Failed => info!("fails even with the slowest preset"),
+                        SpeedGrade::Unsupported => {}
+                    }
+                    link.send(Event::Graded(grade));
+                }
+                Mode::AccessTimes => {
+                    let access_times = mem.measure_access_times();
+                    if let Some(AccessTimes {
+                        t_rac,
+                        t_cac,
+                        uncertainty,
+                    }) = access_times
+                    {
+                        info!(
+                            "tRAC: {} ns, tCAC: {} ns (±{} ns)",
+                            t_rac, t_cac, uncertainty
+                        );
+                    }
+                    link.send(Event::AccessTimes(access_times));
+                }
+                Mode::Test => test_pass(&mut mem, settings.test, &mut pass_count, &mut link),
+            }
+
+            passes += 1;
+            if Some(passes) == settings.loops {
+                info!("run done after {} passes", passes);
+                settings.running = false;
+                passes = 0;
+                link.send(Event::Settings(settings));
+            }
+        }
+    }
+}
+
+/// Applies the commands from the UI (menu or console) to `mem`, the system clock and `settings`,
+/// and reports the new settings. Returns whether there were any.
+fn apply_commands<M: MemoryUnderTest>(
+    mem: &mut M,
+    settings: &mut Settings,
+    sys_clocks: &mut clocks::Clocks,
+    link: &mut ui::Link,
+) -> bool {
+    let mut any = false;
+    while let Some(command) = link.command() {
+        match command {
+            Command::Next(Setting::Test) => {
+                settings.test = settings.test.next();
+                info!("test: {}", settings.test.name());
+            }
+            Command::Next(Setting::Chip) => {
+                if let Some(chip_type) = mem.chip_type() {
+                    let chip_type = chip_type.next();
+                    info!("chip type: {}", chip_type.name());
+                    mem.set_chip_type(chip_type);
+                }
+            }
+            Command::Next(Setting::Preset) => {
+                if let Some(preset) = mem.timing_preset() {
+                    let preset = preset.next();
+                    info!("timing preset: {}", preset.name());
+                    mem.set_timing_preset(preset);
+                }
+            }
+            Command::Next(Setting::Clock) => {
+                let previous = clocks::current();
+                let clock = previous.next();
+                info!("system clock: {}", clock.name());
+                if let Err(err) = sys_clocks.reclock(clock) {
+                    // refuse to test with wrong timings, the previous clock passed
+                    error!(
+                        "delay self-test failed at {}: {} cycles instead of {}",
+                        clock.name(),
+                        err.measured,
+                        err.expected
+                    );
+                    if let Err(err) = sys_clocks.reclock(previous) {
+                        error!(
+                            "delay self-test failed at {} too: {} cycles instead of {}",
+                            previous.name(),
+                            err.measured,
+                            err.expected
+                        );
+                        // refuse to test at all, like after a failed self-test at startup
+                        link.send(Event::SelfTestFault(err));
+                        loop {
+                            cortex_m::asm::wfi();
+                        }
+                    }
+                }
+            }
+            Command::Next(Setting::Loops) => {
+                let i = LOOPS.iter().position(|&loops| loops == settings.loops);
+                settings.loops = LOOPS[i.map_or(0, |i| (i + 1) % LOOPS.len())];
+            }
+            Command::StartStop => {
+                settings.running = !settings.running;
+                info!(
+                    "run {}",
+                    if settings.running {
+                        "started"
+                    } else {
+                        "stopped"
+                    }
+                );
+            }
+            Command::Run(test) => {
+                settings.test = test;
+                settings.running = true;
+                info!("run of {} started", test.name());
+            }
+            Command::Stop => {
+                settings.running = false;
+                info!("run stopped");
+            }
+            Command::SetPreset(preset) => {
+                if mem.timing_preset().is_some() {
+                    info!("timing preset: {}", preset.name());
+                    mem.set_timing_preset(preset);
+                }
+            }
+            Command::SetLoops(loops) => {
+                settings.loops = loops;
+            }
+        }
+        any = true;
+    }
+
+    if any {
+        settings.chip_type = mem.chip_type();
+        settings.preset = mem.timing_preset();
+        settings.clock = clocks::current();
+        link.send(Event::Settings(*settings));
+    }
+    any
+}
+
+/// Runs one pass of `test` on `mem` and reports the result. `pass_count` counts the passes in a
+/// row.
+fn test_pass<M: MemoryUnderTest>(
+    mem: &mut M,
+    test: march::Test,
+    pass_count: &mut u32,
+    link: &mut ui::Link,
+) {
+    match mem.run_test(test.elements()) {
+        Ok(()) => {
+            *pass_count += 1;
+            info!("PASS #{}\n\n", *pass_count);
+            link.send(Event::Passed {
+                pass_count: *pass_count,
+            });
+        }
+        Err(TestError {
+            num_failed_bits,
+            step,
+            row,
+            col,
+            fails_per_bit,
+            map,
+        }) => {
+            *pass_count = 0;
+
+            let addr = row * mem.cols() + col;
+
+            info!(
+                "{} broken bits\nlast failed bit: row {}, col {} (addr {})\n\n",
+                num_failed_bits, row, col, addr
+            );
+
+            let mut fails_per_chip = [0; MAX_DATA_BITS + 1];
+            for (bit, fails) in fails_per_bit.into_iter().enumerate() {
+                if let Some(pos) = mem.chip_position(bit as u8) {
+                    fails_per_chip[usize::from(pos)] += fails;
+                }
+            }
+            for (pos, &fails) in fails_per_chip.iter().enumerate() {
+                if fails > 0 {
+                    info!("U{}: {} broken bits", pos, fails);
+                }
+            }
+
+            link.send(Event::Failed(Failure {
+                num_failed_bits,
+                step,
+                addr,
+                map,
+                fails_per_chip,
+            }));
+        }
+    }
+}
@@ -0,0 +1,615 @@
+//! 4164/41256 DRAM cycles generated by a PIO state machine instead of bit-banging.
+//!
+//! The CPU only prepares the accesses of a whole row as a list of commands, which DMA feeds into
+//! the state machine, and the read data comes back the same way. The timing is deterministic (in
+//! steps of one PIO cycle) and not affected by interrupts or flash cache misses.
+//!
+//! There are two buffers each for the commands and the results, so that the CPU prepares the next
+//! row and checks the results of the previous one while the state machine works on a row.
+//!
+//! Rows are refreshed in turn like with bit-banging, by a RAS-only refresh command after each
+//! burst. If the CPU falls behind, the state machine runs out of commands, so the CPU keeps it
+//! refreshing meanwhile, see [`PioDram::keep_refreshing`].
+//!
+//! Uses the same pins as [`Dram41XX`](crate::dram::Dram41XX): A0-A8 on GPIO0-GPIO8, ~WRT, ~CAS,
+//! ~RAS and DIN on GPIO11-GPIO14, DOUT on GPIO15.
+
+use cortex_m::singleton;
+use pio::{Assembler, InSource, JmpCondition, OutDestination, SetDestination};
+
+use crate::{
+    chipdb::ChipType,
+    clocks::{self, with_clock, ClockPreset, SysClock},
+    delay, delay_ns,
+    hal::{
+        dma::{single_buffer, Channel, CH0, CH1},
+        pio::{
+            PIOBuilder, PinDir, PinState, Running, Rx, ShiftDirection, StateMachine, Tx,
+            UninitStateMachine, PIO, SM0,
+        },
+    },
+    march::{self, Element, Op},
+    memory::{DetectError, FailureMap, MemoryUnderTest, TestError, MAX_DATA_BITS},
+    pac::PIO0,
+    progress,
+    timings::{with_preset, DramTimingConfig, TimingPreset},
+};
+
+type Sm = (PIO0, SM0);
+type TxTransfer = single_buffer::Transfer<Channel<CH0>, &'static [u32], Tx<Sm>>;
+type RxTransfer = single_buffer::Transfer<Channel<CH1>, Rx<Sm>, &'static mut [u32]>;
+
+/// Number of address lines driven by the state machine (GPIO0-GPIO8)
+const ADDR_PINS: u8 = 9;
+/// First of the ~WRT, ~CAS, ~RAS and DIN pins
+const SET_PIN_BASE: u8 = 11;
+const RAS_PIN: u8 = SET_PIN_BASE + 2;
+const DOUT_PIN: u8 = 15;
+
+// values of `set pins`
+const WE_HIGH: u8 = 1 << 0;
+const CAS_HIGH: u8 = 1 << 1;
+const RAS_HIGH: u8 = 1 << 2;
+const DIN_HIGH: u8 = 1 << 3;
+
+// column command: bits 0-8 column address, bit 9 set for writes, bit 10 data to write
+const WRITE: u32 = 1 << 9;
+const DATA: u32 = 1 << 10;
+// row command: bits 0-8 row address, bit 9 set for a RAS-only refresh cycle, bits 16-31 number
+// of following column commands - 1
+const REFRESH: u32 = 1 << 9;
+const COUNT_SHIFT: u32 = 16;
+
+const MAX_COLS: usize = 512;
+/// Maximum number of operations per element of a march test
+const MAX_OPS: usize = 4;
+const MAX_ACCESSES: usize = MAX_COLS * MAX_OPS;
+/// Fewest accesses per RAS pulse of any timing preset (see [`DramTimingConfig::MAX_BURST`]), each
+/// burst takes a row command and a refresh command
+const MIN_BURST: usize = 8;
+const MAX_COMMANDS: usize = MAX_ACCESSES + 2 * MAX_ACCESSES.div_ceil(MIN_BURST);
+
+pub struct PioDram {
+    pio: PIO<PIO0>,
+    sm: Option<StateMachine<Sm, Running>>,
+    /// The FIFOs and their DMA channels, unless a transfer has them
+    tx: Option<(Tx<Sm>, Channel<CH0>)>,
+    rx: Option<(Rx<Sm>, Channel<CH1>)>,
+    /// The transfer in progress, see [`PioDram::start`]
+    tx_transfer: Option<TxTransfer>,
+    rx_transfer: Option<RxTransfer>,
+    commands: &'static mut [[u32; MAX_COMMANDS]; 2],
+    results: &'static mut [[u32; MAX_ACCESSES]; 2],
+    num_addr_lines: u8,
+    chip_type: ChipType,
+    preset: TimingPreset,
+    /// Clock the program was assembled for
+    clock: ClockPreset,
+    /// Accesses per RAS pulse with the timings of the program
+    max_burst: usize,
+    row: usize,
+    /// The row to refresh next
+    refresh_row: usize,
+}
+
+/// The failures of a pass so far
+struct Failures {
+    num_failed_bits: usize,
+    last_failed_bit: Option<(usize, usize)>,
+    map: FailureMap,
+}
+
+impl PioDram {
+    pub fn new(
+        pio: PIO<PIO0>,
+        sm: UninitStateMachine<Sm>,
+        tx_ch: Channel<CH0>,
+        rx_ch: Channel<CH1>,
+    ) -> Self {
+        let mut dram = Self {
+            pio,
+            sm: None,
+            tx: None,
+            rx: None,
+            tx_transfer: None,
+            rx_transfer: None,
+            commands: singleton!(: [[u32; MAX_COMMANDS]; 2] = [[0; MAX_COMMANDS]; 2]).unwrap(),
+            results: singleton!(: [[u32; MAX_ACCESSES]; 2] = [[0; MAX_ACCESSES]; 2]).unwrap(),
+            num_addr_lines: 8,
+            chip_type: ChipType::selected(crate::PART),
+            preset: TimingPreset::Default,
+            clock: clocks::current(),
+            max_burst: MIN_BURST,
+            row: 0,
+            refresh_row: 0,
+        };
+        dram.load(sm, tx_ch, rx_ch);
+        dram
+    }
+
+    /// Installs the program for the current timing preset and clock, and starts the state machine.
+    fn load(&mut self, sm: UninitStateMachine<Sm>, tx_ch: Channel<CH0>, rx_ch: Channel<CH1>) {
+        self.clock = clocks::current();
+        let (program, divisor, max_burst) = with_clock!(self.clock, C => {
+            with_preset!(self.preset, C, T => program::<T>())
+        });
+        self.max_burst = max_burst;
+        let installed = self.pio.install(&program).unwrap();
+
+        let (mut sm, rx, tx) = PIOBuilder::from_program(installed)
+            .out_pins(0, ADDR_PINS)
+            .set_pins(SET_PIN_BASE, 4)
+            .in_pin_base(DOUT_PIN)
+            .out_shift_direction(ShiftDirection::Right)
+            .in_shift_direction(ShiftDirection::Left)
+            .autopush(true)
+            .push_threshold(1)
+            .clock_divisor_fixed_point(divisor, 0)
+            .build(sm);
+
+        // strobes are active low, don't glitch them when switching to outputs
+        sm.set_pins([
+            (SET_PIN_BASE, PinState::High),
+            (SET_PIN_BASE + 1, PinState::High),
+            (RAS_PIN, PinState::High),
+        ]);
+        sm.set_pindirs(
+            (0..ADDR_PINS)
+                .chain(SET_PIN_BASE..SET_PIN_BASE + 4)
+                .map(|pin| (pin, PinDir::Output)),
+        );
+
+        self.sm = Some(sm.start());
+        self.tx = Some((tx, tx_ch));
+        self.rx = Some((rx, rx_ch));
+    }
+
+    fn reload(&mut self) {
+        self.finish();
+        let (tx, tx_ch) = self.tx.take().unwrap();
+        let (rx, rx_ch) = self.rx.take().unwrap();
+        let (sm, program) = self.sm.take().unwrap().stop().uninit(rx, tx);
+        self.pio.uninstall(program);
+        self.load(sm, tx_ch, rx_ch);
+    }
+
+    /// Reloads the program if the clock changed, as the delays are counted in cycles.
+    fn follow_clock(&mut self) {
+        if self.clock != clocks::current() {
+            self.reload();
+        }
+    }
+
+    /// Starts feeding the first `num_commands` of command buffer `buffer` to the state machine, and
+    /// collecting `num_results` read results in result buffer `buffer`. The transfer before has to
+    /// be finished.
+    fn start(&mut self, buffer: usize, num_commands: usize, num_results: usize) {
+        let (tx, tx_ch) = self.tx.take().unwrap();
+        let (rx, rx_ch) = self.rx.take().unwrap();
+
+        // SAFETY: the buffers aren't accessed otherwise until the transfer is finished
+        let (commands, results) = unsafe {
+            (
+                core::slice::from_raw_parts(self.commands[buffer].as_ptr(), num_commands),
+                core::slice::from_raw_parts_mut(self.results[buffer].as_mut_ptr(), num_results),
+            )
+        };
+
+        self.rx_transfer = Some(single_buffer::Config::new(rx_ch, rx, results).start());
+        self.tx_transfer = Some(single_buffer::Config::new(tx_ch, commands, tx).start());
+    }
+
+    /// Waits for the transfer in progress, if any, to finish.
+    fn finish(&mut self) {
+        if let Some(transfer) = self.tx_transfer.take() {
+            let (tx_ch, _, tx) = transfer.wait();
+            self.tx = Some((tx, tx_ch));
+        }
+        if let Some(transfer) = self.rx_transfer.take() {
+            let (rx_ch, rx, _) = transfer.wait();
+            self.rx = Some((rx, rx_ch));
+        }
+    }
+
+    /// Feeds the first `num_commands` of the first command buffer to the state machine, and
+    /// collects `num_results` read results in the first result buffer.
+    fn transfer(&mut self, num_commands: usize, num_results: usize) {
+        self.finish();
+        self.follow_clock();
+        self.start(0, num_commands, num_results);
+        self.finish();
+    }
+
+    /// Refreshes the next row if the state machine ran out of commands, so that the rows are still
+    /// refreshed in turn while the CPU is behind. Has to be called at least once per burst of the
+    /// CPU's work, which takes no longer than bit-banging the burst.
+    ///
+    /// The refresh commands that a transfer brings along are only numbered when it starts (see
+    /// [`PioDram::number_refreshes`]), so that the rows are refreshed in the order they're taken.
+    #[inline(always)]
+    fn keep_refreshing(&mut self) {
+        if self
+            .tx_transfer
+            .as_ref()
+            .is_some_and(|transfer| transfer.is_done())
+        {
+            let (tx_ch, _, tx) = self.tx_transfer.take().unwrap().wait();
+            self.tx = Some((tx, tx_ch));
+        }
+        if self.tx.as_ref().is_some_and(|(tx, _)| tx.is_empty()) {
+            let command = self.refresh_command();
+            self.tx.as_mut().unwrap().0.write(command);
+        }
+    }
+
+    /// A refresh command for the next row in turn
+    fn refresh_command(&mut self) -> u32 {
+        let row = self.refresh_row;
+        self.refresh_row = (row + 1) & (self.rows() - 1);
+        row as u32 | REFRESH
+    }
+
+    /// Puts the commands for `element` on the `i`th row into command buffer `i % 2`, and returns
+    /// their number. Each burst reopens the row with a row command and is followed by a refresh
+    /// command, which is numbered later on.
+    fn prepare(&mut self, element: &Element, i: usize) -> usize {
+        let (rows, cols) = (self.rows(), self.cols());
+        let row = element.order.index(i, rows);
+        let buffer = i % 2;
+
+        let mut num_commands = 0;
+        let mut burst_start = 0;
+        let mut burst = 0;
+        for j in 0..cols {
+            let col = element.order.index(j, cols);
+            let background = march::background(col, 1);
+
+            for &op in element.ops {
+                if burst == 0 {
+                    burst_start = num_commands;
+                    num_commands += 1;
+                }
+                self.commands[buffer][num_commands] = match op {
+                    Op::Write(inverted) => write_command(col, background ^ u32::from(inverted)),
+                    Op::Read(_) => col as u32,
+                };
+                num_commands += 1;
+                burst += 1;
+
+                if burst == self.max_burst {
+                    self.commands[buffer][burst_start] = row_command(row, burst);
+                    num_commands += 1;
+                    burst = 0;
+                    self.keep_refreshing();
+                }
+            }
+        }
+        if burst > 0 {
+            self.commands[buffer][burst_start] = row_command(row, burst);
+            num_commands += 1;
+        }
+        num_commands
+    }
+
+    /// Fills in the rows of the refresh commands that [`PioDram::prepare`] left after each burst.
+    fn number_refreshes(&mut self, buffer: usize, num_commands: usize) {
+        // a full burst takes a row command, the accesses and the refresh command, the last burst
+        // may be shorter
+        let burst_len = self.max_burst + 2;
+        for burst_start in (0..num_commands).step_by(burst_len) {
+            let refresh = (burst_start + burst_len).min(num_commands) - 1;
+            self.commands[buffer][refresh] = self.refresh_command();
+        }
+    }
+
+    /// Checks the results of `element` on the `i`th row in result buffer `i % 2`.
+    fn check(&mut self, element: &Element, i: usize, failures: &mut Failures) {
+        let (rows, cols) = (self.rows(), self.cols());
+        let row = element.order.index(i, rows);
+
+        let mut n = 0;
+        let mut until_refresh = self.max_burst;
+        for j in 0..cols {
+            let col = element.order.index(j, cols);
+            let background = march::background(col, 1);
+
+            for &op in element.ops {
+                if let Op::Read(inverted) = op {
+                    if self.results[i % 2][n] != background ^ u32::from(inverted) {
+                        failures.num_failed_bits += 1;
+                        failures.last_failed_bit = Some((row, col));
+                        failures.map.mark(row, col);
+                    }
+                    n += 1;
+                }
+                until_refresh -= 1;
+                if until_refresh == 0 {
+                    self.keep_refreshing();
+                    until_refresh = self.max_burst;
+                }
+            }
+        }
+        progress::rows_done(i + 1);
+    }
+
+    fn write_one_word(&mut self, row: usize, col: usize, word: u32) {
+        self.commands[0][0] = row_command(row, 1);
+        self.commands[0][1] = write_command(col, word);
+        self.transfer(2, 0);
+    }
+
+    fn read_one_word(&mut self, row: usize, col: usize) -> u32 {
+        self.commands[0][0] = row_command(row, 1);
+        self.commands[0][1] = col as u32;
+        self.transfer(2, 1);
+        self.results[0][0]
+    }
+}
+
+impl MemoryUnderTest for PioDram {
+    fn init(&mut self) {
+        // power-up pause, like `Dram41XX`
+        delay_ns::<1000_0000>();
+
+        // wake-up cycles, RAS only: set from the stopped state machine, which is between two rows
+        self.finish();
+        let mut sm = self.sm.take().unwrap().stop();
+        for _ in 0..8 {
+            sm.set_pins([(RAS_PIN, PinState::Low)]);
+            delay_ns::<1000>();
+            sm.set_pins([(RAS_PIN, PinState::High)]);
+            delay_ns::<1000>();
+        }
+        self.sm = Some(sm.start());
+    }
+
+    fn detect(&mut self) -> Result<&'static str, DetectError> {
+        self.write_one_word(0, 0, 0);
+        let zero = self.read_one_word(0, 0);
+        self.write_one_word(0, 0, 1);
+        let one = self.read_one_word(0, 0);
+        if zero != 0 || one != 1 {
+            return Err(DetectError::NoChip);
+        }
+
+        // A8 isn't connected on a 4164, so the address space wraps around
+        self.write_one_word(8, 8, 0);
+        self.write_one_word(256 + 8, 256 + 8, 1);
+        let (num_addr_lines, name) = if self.read_one_word(8, 8) != 0 {
+            (8, "4164")
+        } else {
+            (9, "41256")
+        };
+        self.num_addr_lines = num_addr_lines;
+
+        self.chip_type.check(num_addr_lines, name)
+    }
+
+    fn rows(&self) -> usize {
+        1 << self.num_addr_lines
+    }
+
+    fn cols(&self) -> usize {
+        1 << self.num_addr_lines
+    }
+
+    fn data_bits(&self) -> u8 {
+        1
+    }
+
+    // Single accesses (only used outside of `run_test`) each get their own RAS cycle.
+
+    fn open_row(&mut self, row: usize) {
+        self.row = row;
+    }
+
+    fn close_row(&mut self) {}
+
+    fn write(&mut self, col: usize, word: u32) {
+        self.write_one_word(self.row, col, word);
+    }
+
+    fn read(&mut self, col: usize) -> u32 {
+        self.read_one_word(self.row, col)
+    }
+
+    /// Same as [`march::run`], but processes whole rows at once. The remaining operations on a
+    /// word are still executed after a failed read.
+    fn run_test(&mut self, test: &[Element]) -> Result<(), TestError> {
+        let rows = self.rows();
+        let cols = self.cols();
+        self.finish();
+        self.follow_clock();
+
+        let mut failures = Failures {
+            num_failed_bits: 0,
+            last_failed_bit: None,
+            map: FailureMap::new(rows, cols),
+        };
+        let mut failed_step = 0;
+
+        progress::start(test.len(), rows);
+        for (step, element) in test.iter().enumerate() {
+            progress::step(step);
+            assert!(element.ops.len() <= MAX_OPS);
+            let reads_per_col = element
+                .ops
+                .iter()
+                .filter(|op| matches!(op, Op::Read(_)))
+                .count();
+
+            for i in 0..rows {
+                // while the row before is processed, check the one before that, whose buffers this
+                // row takes over, and prepare this one
+                if i >= 2 {
+                    self.check(element, i - 2, &mut failures);
+                }
+                let num_commands = self.prepare(element, i);
+                self.number_refreshes(i % 2, num_commands);
+                self.finish();
+                self.start(i % 2, num_commands, cols * reads_per_col);
+            }
+            self.finish();
+            for i in rows - 2..rows {
+                self.check(element, i, &mut failures);
+            }
+
+            if failures.num_failed_bits > 0 {
+                failed_step = step;
+                break;
+            }
+        }
+        progress::finish();
+
+        match failures.last_failed_bit {
+            None => Ok(()),
+            Some((row, col)) => {
+                let mut fails_per_bit = [0; MAX_DATA_BITS];
+                fails_per_bit[0] = failures.num_failed_bits;
+                Err(TestError {
+                    num_failed_bits: failures.num_failed_bits,
+                    step: failed_step,
+                    row,
+                    col,
+                    fails_per_bit,
+                    map: failures.map,
+                })
+            }
+        }
+    }
+
+    fn set_chip_type(&mut self, chip_type: ChipType) {
+        self.chip_type = chip_type;
+    }
+
+    fn chip_type(&self) -> Option<ChipType> {
+        Some(self.chip_type)
+    }
+
+    fn set_timing_preset(&mut self, preset: TimingPreset) {
+        if preset == self.preset {
+            return;
+        }
+        self.preset = preset;
+        self.reload();
+    }
+
+    fn timing_preset(&self) -> Option<TimingPreset> {
+        Some(self.preset)
+    }
+}
+
+fn row_command(row: usize, num_commands: usize) -> u32 {
+    row as u32 | ((num_commands as u32 - 1) << COUNT_SHIFT)
+}
+
+fn write_command(col: usize, word: u32) -> u32 {
+    col as u32 | WRITE | if word & 1 != 0 { DATA } else { 0 }
+}
+
+/// Assembles the cycle generator with the delays of `T`. Returns it along with the clock divisor
+/// of the state machine and the number of accesses per RAS pulse.
+fn program<T: DramTimingConfig>() -> (pio::Program<{ pio::RP2040_MAX_PROGRAM_SIZE }>, u16, usize) {
+    let () = T::ASSERT_VALID;
+    // the PIO takes fewer cycles per access than the CPU, which the burst length is computed for
+    const {
+        assert!(
+            T::MAX_BURST as usize >= MIN_BURST,
+            "too few accesses per RAS pulse"
+        )
+    };
+
+    // the next edge is made by the instruction after the delayed one, so a delay of up to 31 cycles
+    // covers 32 cycles from edge to edge. Slow the state machine down if a delay doesn't fit.
+    let longest = [
+        T::T_RCD,
+        T::T_CAS + T::T_TRANSCEIVER,
+        T::T_CP,
+        T::T_RAS_REST,
+        T::T_RP,
+    ]
+    .into_iter()
+    .max()
+    .unwrap();
+    let cycles = |ns: u32| delay::ns_to_cycles(ns, <T::Clock as SysClock>::FREQ);
+    let divisor = cycles(longest).div_ceil(32).max(1);
+    let delay = |ns: u32| (cycles(ns).div_ceil(divisor).saturating_sub(1)) as u8;
+
+    let mut a = Assembler::<{ pio::RP2040_MAX_PROGRAM_SIZE }>::new();
+    let mut wrap_target = a.label();
+    let mut wrap_source = a.label();
+    let mut column = a.label();
+    let mut write_zero = a.label();
+    let mut read = a.label();
+    let mut refresh = a.label();
+    let mut close = a.label();
+
+    // open the row
+    a.bind(&mut wrap_target);
+    a.pull(false, true);
+    a.out(OutDestination::PINS, ADDR_PINS);
+    a.out(OutDestination::X, 1);
+    a.out(OutDestination::NULL, COUNT_SHIFT as u8 - ADDR_PINS - 1);
+    a.out(OutDestination::Y, 32 - COUNT_SHIFT as u8);
+    a.set_with_delay(SetDestination::PINS, WE_HIGH | CAS_HIGH, delay(T::T_RCD));
+    // which makes RAS to CAS a cycle longer for the accesses
+    a.jmp(JmpCondition::XDecNonZero, &mut refresh);
+
+    a.bind(&mut column);
+    a.pull(false, true);
+    a.out(OutDestination::PINS, ADDR_PINS);
+    a.out(OutDestination::X, 1);
+    a.jmp(JmpCondition::XIsZero, &mut read);
+
+    // early write cycle, both branches take the same time
+    a.out(OutDestination::X, 1);
+    a.jmp(JmpCondition::XIsZero, &mut write_zero);
+    a.set(SetDestination::PINS, DIN_HIGH | CAS_HIGH);
+    a.set_with_delay(SetDestination::PINS, DIN_HIGH, delay(T::T_CAS));
+    a.set_with_delay(
+        SetDestination::PINS,
+        DIN_HIGH | WE_HIGH | CAS_HIGH,
+        delay(T::T_CP),
+    );
+    a.jmp(JmpCondition::YDecNonZero, &mut column);
+    a.jmp(JmpCondition::Always, &mut close);
+
+    a.bind(&mut write_zero);
+    a.set(SetDestination::PINS, CAS_HIGH);
+    a.set_with_delay(SetDestination::PINS, 0, delay(T::T_CAS));
+    a.set_with_delay(SetDestination::PINS, WE_HIGH | CAS_HIGH, delay(T::T_CP));
+    a.jmp(JmpCondition::YDecNonZero, &mut column);
+    a.jmp(JmpCondition::Always, &mut close);
+
+    // RAS-only refresh cycle, RAS stays low for tRAS like with a single access
+    a.bind(&mut refresh);
+    a.nop_with_delay(delay(T::T_CAS));
+    a.jmp(JmpCondition::Always, &mut close);
+
+    // read cycle, accounting for bus transceiver delay
+    a.bind(&mut read);
+    a.set_with_delay(
+        SetDestination::PINS,
+        WE_HIGH,
+        delay(T::T_CAS + T::T_TRANSCEIVER),
+    );
+    a.in_(InSource::PINS, 1);
+    a.set_with_delay(SetDestination::PINS, WE_HIGH | CAS_HIGH, delay(T::T_CP));
+    a.jmp(JmpCondition::YDecNonZero, &mut column);
+
+    // close the row
+    a.bind(&mut close);
+    a.nop_with_delay(delay(T::T_RAS_REST));
+    a.set_with_delay(
+        SetDestination::PINS,
+        WE_HIGH | CAS_HIGH | RAS_HIGH,
+        delay(T::T_RP),
+    );
+    a.bind(&mut wrap_source);
+
+    (
+        a.assemble_with_wrap(wrap_source, wrap_target),
+        divisor as u16,
+        T::MAX_BURST as usize,
+    )
+}
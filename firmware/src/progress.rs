@@ -0,0 +1,13 @@
+//! The timer of the firmware, and the [progress](picoram_core::progress) of the running pass
+//! timed by it.
+
+pub use picoram_core::progress::*;
+
+use crate::pac;
+
+/// Microseconds since boot, wrapping after ~71 minutes. Reads the timer without owning it, so it
+/// can be used from both cores.
+#[inline(always)]
+pub fn now_us() -> u32 {
+    unsafe { (*pac::TIMER::ptr()).timerawl.read().bits() }
+}
@@ -0,0 +1,124 @@
+//! Data lines of 30-pin 256K/1M ×8 (+ parity) SIMMs.
+//!
+//! The module is driven by [`Dram41XX`](crate::dram::Dram41XX), with A0-A9 on GPIO0-GPIO9. The
+//! parity chip has separate PD/PQ pins, which are wired up like DIN/DOUT of a single chip (GPIO14
+//! and GPIO15). DQ0-DQ7 are bidirectional and take up the remaining GPIOs, see [`DQ_PINS`].
+
+use eh1_0_alpha::digital::{InputPin, OutputPin};
+
+use crate::{dram::DataLines, pac};
+
+/// GPIOs connected to DQ0-DQ7
+pub const DQ_PINS: [u8; 8] = [17, 18, 19, 20, 21, 22, 10, 28];
+
+const DQ_MASK: u32 = {
+    let mut mask = 0;
+    let mut i = 0;
+    while i < DQ_PINS.len() {
+        mask |= 1 << DQ_PINS[i];
+        i += 1;
+    }
+    mask
+};
+
+/// Data bit of the parity chip
+const PARITY_BIT: u8 = 8;
+
+/// Assignment of data bits to the chips on a module, to report failures per chip. Also decides
+/// whether the parity bit is tested, so that a dead parity chip shows up as failures.
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+pub enum SimmLayout {
+    /// One ×1 chip per bit: U1-U8 hold DQ0-DQ7, U9 holds the parity bit
+    NineChip,
+    /// Two ×4 chips (U1: DQ0-DQ3, U2: DQ4-DQ7), U3 holds the parity bit
+    ThreeChip,
+    /// Like [`Self::NineChip`], without the parity chip
+    EightChipNoParity,
+    /// Like [`Self::ThreeChip`], without the parity chip
+    TwoChipNoParity,
+}
+
+impl SimmLayout {
+    fn chip_position(self, bit: u8) -> u8 {
+        match self {
+            SimmLayout::NineChip | SimmLayout::EightChipNoParity => bit + 1,
+            SimmLayout::ThreeChip | SimmLayout::TwoChipNoParity => bit / 4 + 1,
+        }
+    }
+
+    fn data_bits(self) -> u8 {
+        match self {
+            SimmLayout::NineChip | SimmLayout::ThreeChip => PARITY_BIT + 1,
+            SimmLayout::EightChipNoParity | SimmLayout::TwoChipNoParity => PARITY_BIT,
+        }
+    }
+}
+
+pub struct SimmData<Pd, Pq> {
+    sio: pac::SIO,
+    pd: Pd,
+    pq: Pq,
+    layout: SimmLayout,
+}
+
+impl<Pd, Pq> SimmData<Pd, Pq> {
+    pub fn new(sio: pac::SIO, pd: Pd, pq: Pq, layout: SimmLayout) -> Self {
+        Self {
+            sio,
+            pd,
+            pq,
+            layout,
+        }
+    }
+}
+
+impl<Pd: OutputPin, Pq: InputPin> DataLines for SimmData<Pd, Pq> {
+    const WIDTH: u8 = 9;
+    const SIZES: &'static [(u8, &'static str)] = &[(9, "256K SIMM"), (10, "1M SIMM")];
+
+    #[inline(always)]
+    fn set(&mut self, word: u32) {
+        let mut out = 0;
+        for (bit, pin) in DQ_PINS.iter().enumerate() {
+            out |= ((word >> bit) & 1) << pin;
+        }
+
+        // DQ pins are only touched through the set/clear aliases, so the address bus (which uses
+        // the XOR alias) isn't affected
+        self.sio
+            .gpio_out_clr
+            .write(|f| unsafe { f.bits(!out & DQ_MASK) });
+        self.sio.gpio_out_set.write(|f| unsafe { f.bits(out) });
+        self.sio.gpio_oe_set.write(|f| unsafe { f.bits(DQ_MASK) });
+
+        self.pd
+            .set_state((word & (1 << PARITY_BIT) != 0).into())
+            .unwrap();
+    }
+
+    #[inline(always)]
+    fn release(&mut self) {
+        self.sio.gpio_oe_clr.write(|f| unsafe { f.bits(DQ_MASK) });
+    }
+
+    #[inline(always)]
+    fn get(&mut self) -> u32 {
+        let input = self.sio.gpio_in.read().bits();
+
+        let mut word = 0;
+        for (bit, pin) in DQ_PINS.iter().enumerate() {
+            word |= ((input >> pin) & 1) << bit;
+        }
+
+        word | (u32::from(self.pq.is_high().unwrap()) << PARITY_BIT)
+    }
+
+    fn width(&self) -> u8 {
+        self.layout.data_bits()
+    }
+
+    fn chip_position(&self, bit: u8) -> Option<u8> {
+        Some(self.layout.chip_position(bit))
+    }
+}
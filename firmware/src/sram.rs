@@ -0,0 +1,250 @@
+//! Driver for 6116/6264/62256-style SRAMs (non-multiplexed address, 8 bits of data).
+//!
+//! The address is shifted into two daisy-chained 74HCT595s, the data bus is directly connected to
+//! GPIO0-GPIO7.
+//!
+//! Pin 26 is A13 on a 62256, but CE2 on a 6264, so the adapter has to keep it high when testing a
+//! 6264.
+
+use eh1_0_alpha::digital::OutputPin;
+
+use crate::{
+    delay_ns,
+    memory::{DetectError, MemoryUnderTest},
+    pac,
+    timings::{SramTimingConfig, PIN_WRITE_CYCLES},
+    SramTimings, ADDR_SETTLE,
+};
+
+/// Minimum pulse duration of SRCLK/RCLK of the 74HCT595 (with some margin).
+pub const SHIFT_PULSE: u32 = 25;
+
+/// Number of columns per row, i.e. the lower 8 address bits.
+const COL_BITS: u8 = 8;
+
+pub struct Sram62XX<Ce, Oe, We, Ser, Srclk, Rclk> {
+    ce: Ce,
+    oe: Oe,
+    we: We,
+    addr: ShiftRegister<Ser, Srclk, Rclk>,
+    data: DataBus,
+    num_addr_lines: u8,
+    row: usize,
+}
+
+impl<Ce, Oe, We, Ser, Srclk, Rclk> Sram62XX<Ce, Oe, We, Ser, Srclk, Rclk>
+where
+    Ce: OutputPin,
+    Oe: OutputPin,
+    We: OutputPin,
+    Ser: OutputPin,
+    Srclk: OutputPin,
+    Rclk: OutputPin,
+{
+    pub fn new(sio: pac::SIO, ce: Ce, oe: Oe, we: We, ser: Ser, srclk: Srclk, rclk: Rclk) -> Self {
+        Self {
+            ce,
+            oe,
+            we,
+            addr: ShiftRegister { ser, srclk, rclk },
+            data: DataBus { sio },
+            num_addr_lines: 11,
+            row: 0,
+        }
+    }
+
+    fn is_working(&mut self) -> bool {
+        for pat in [0x55, 0xAA] {
+            self.write_at(0, pat);
+            if self.read_at(0) != pat {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Finds the number of address lines by checking where the address space wraps around.
+    fn detect_addr_lines(&mut self) -> u8 {
+        self.write_at(0, 0);
+
+        for (num_addr_lines, marker) in [(11, 0xA5), (13, 0x5A)] {
+            self.write_at(1 << num_addr_lines, marker);
+            if self.read_at(0) == marker {
+                // wrapped around
+                return num_addr_lines;
+            }
+        }
+
+        15
+    }
+
+    fn write_at(&mut self, addr: usize, byte: u8) {
+        self.ce.set_low().unwrap();
+        self.write_cycle(addr, byte);
+        self.ce.set_high().unwrap();
+    }
+
+    fn read_at(&mut self, addr: usize) -> u8 {
+        self.ce.set_low().unwrap();
+        let byte = self.read_cycle(addr);
+        self.ce.set_high().unwrap();
+        byte
+    }
+
+    /// WE-controlled write cycle, expects CE to be low already.
+    fn write_cycle(&mut self, addr: usize, byte: u8) {
+        self.addr.set(addr);
+        self.data.drive(byte);
+        self.we.set_low().unwrap();
+        SramTimings::delay_edges(SramTimings::T_WP, PIN_WRITE_CYCLES);
+        self.we.set_high().unwrap();
+        SramTimings::delay_edges(SramTimings::T_WR, PIN_WRITE_CYCLES);
+        self.data.release();
+    }
+
+    /// OE-controlled read cycle, expects CE to be low already.
+    fn read_cycle(&mut self, addr: usize) -> u8 {
+        let () = SramTimings::ASSERT_VALID;
+
+        self.addr.set(addr);
+        self.oe.set_low().unwrap();
+        // from latching the address: through the shift register (less the RCLK pulse that `set`
+        // waited, and the writes to RCLK and OE since), the SRAM and the bus transceivers
+        SramTimings::delay_edges(
+            SramTimings::T_ADDR_DELAY + SramTimings::T_AA + SramTimings::T_TRANSCEIVER,
+            2 * PIN_WRITE_CYCLES,
+        );
+
+        let byte = self.data.read();
+        self.oe.set_high().unwrap();
+        byte
+    }
+}
+
+impl<Ce, Oe, We, Ser, Srclk, Rclk> MemoryUnderTest for Sram62XX<Ce, Oe, We, Ser, Srclk, Rclk>
+where
+    Ce: OutputPin,
+    Oe: OutputPin,
+    We: OutputPin,
+    Ser: OutputPin,
+    Srclk: OutputPin,
+    Rclk: OutputPin,
+{
+    fn init(&mut self) {
+        self.ce.set_high().unwrap();
+        self.oe.set_high().unwrap();
+        self.we.set_high().unwrap();
+        self.data.release();
+    }
+
+    fn detect(&mut self) -> Result<&'static str, DetectError> {
+        if !self.is_working() {
+            return Err(DetectError::NoChip);
+        }
+
+        self.num_addr_lines = self.detect_addr_lines();
+        Ok(match self.num_addr_lines {
+            11 => "6116",
+            13 => "6264",
+            _ => "62256",
+        })
+    }
+
+    fn rows(&self) -> usize {
+        1 << (self.num_addr_lines - COL_BITS)
+    }
+
+    fn cols(&self) -> usize {
+        1 << COL_BITS
+    }
+
+    fn data_bits(&self) -> u8 {
+        8
+    }
+
+    fn open_row(&mut self, row: usize) {
+        self.row = row << COL_BITS;
+        self.ce.set_low().unwrap();
+    }
+
+    fn close_row(&mut self) {
+        self.ce.set_high().unwrap();
+    }
+
+    fn write(&mut self, col: usize, word: u32) {
+        self.write_cycle(self.row | col, word as u8);
+    }
+
+    fn read(&mut self, col: usize) -> u32 {
+        self.read_cycle(self.row | col).into()
+    }
+}
+
+/// Two daisy-chained 74HCT595s holding the address.
+struct ShiftRegister<Ser, Srclk, Rclk> {
+    ser: Ser,
+    srclk: Srclk,
+    rclk: Rclk,
+}
+
+impl<Ser, Srclk, Rclk> ShiftRegister<Ser, Srclk, Rclk>
+where
+    Ser: OutputPin,
+    Srclk: OutputPin,
+    Rclk: OutputPin,
+{
+    fn set(&mut self, addr: usize) {
+        for i in (0..16).rev() {
+            self.ser.set_state((addr & (1 << i) != 0).into()).unwrap();
+            self.srclk.set_high().unwrap();
+            delay_ns::<SHIFT_PULSE>();
+            self.srclk.set_low().unwrap();
+            delay_ns::<SHIFT_PULSE>();
+        }
+
+        // latch all address bits at once
+        self.rclk.set_high().unwrap();
+        delay_ns::<SHIFT_PULSE>();
+        self.rclk.set_low().unwrap();
+
+        delay_ns::<ADDR_SETTLE>();
+    }
+}
+
+/// The data bus on GPIO0-GPIO7.
+///
+/// embedded-hal doesn't support switching pins between input and output at runtime (or setting
+/// multiple pins at once), so this directly accesses the SIO registers instead.
+struct DataBus {
+    sio: pac::SIO,
+}
+
+impl DataBus {
+    const MASK: u32 = 0xFF;
+
+    #[inline(always)]
+    fn drive(&mut self, byte: u8) {
+        self.sio
+            .gpio_out_clr
+            .write(|f| unsafe { f.bits(Self::MASK) });
+        self.sio
+            .gpio_out_set
+            .write(|f| unsafe { f.bits(byte.into()) });
+        self.sio
+            .gpio_oe_set
+            .write(|f| unsafe { f.bits(Self::MASK) });
+    }
+
+    #[inline(always)]
+    fn release(&mut self) {
+        self.sio
+            .gpio_oe_clr
+            .write(|f| unsafe { f.bits(Self::MASK) });
+    }
+
+    #[inline(always)]
+    fn read(&mut self) -> u8 {
+        (self.sio.gpio_in.read().bits() & Self::MASK) as u8
+    }
+}
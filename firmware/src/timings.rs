@@ -0,0 +1,240 @@
+use core::marker::PhantomData;
+
+pub use picoram_core::timings::TimingPreset;
+use picoram_core::timings::{self as core_timings, CycleCosts, NsTimings, SramNsTimings};
+
+use crate::{
+    chipdb,
+    clocks::{self, SysClock},
+    delay, sram, PART, SHIFT_REGISTER_DELAY, TRANSCEIVER_DELAY,
+};
+
+/// CPU cycles of a pin write, counted by [`DramTimingConfig::delay_edges`]. The HAL's
+/// `set_low`/`set_high` compile to a single `str` of the pin mask to the SIO set/clear register,
+/// which takes 1 cycle on the single-cycle IOPORT of the Cortex-M0+. Loading the mask and the SIO
+/// address (`movs`/`lsls`/`ldr`) adds up to 3 cycles, but the compiler hoists those out of loops,
+/// so they aren't counted. [`delay::self_test`] checks this against SysTick.
+pub const PIN_WRITE_CYCLES: u32 = 1;
+
+/// CPU cycles of an address bus write, counted like [`PIN_WRITE_CYCLES`]: `eors` of the new and
+/// the last address (1) and the `str` to the SIO GPIO_OUT_XOR register (1), from the disassembly
+/// of the page mode accesses. The `ldr`/`str` of the last address take 4 more, but the compiler
+/// schedules them before the previous edge or after the next one, so they aren't counted.
+pub const ADDR_WRITE_CYCLES: u32 = 2;
+
+/// CPU cycles of a page mode access besides its delays, at most: about 35 for a read, 20 more for
+/// moving on to the next column and 7 for splitting the burst, from the disassembly of
+/// [`march::run`](crate::march::run), with some margin for the compiler scheduling it differently.
+/// Failures are recorded with the row closed, so they don't count.
+pub const ACCESS_MAX_CYCLES: u32 = 80;
+
+/// CPU cycles from closing a row to reopening it besides the delays, at most: recording a failed
+/// read takes about 50 plus 14 per failed bit, i.e. 180 for the 9 bits of a SIMM, from the
+/// disassembly of [`march::run`](crate::march::run), with some margin like above.
+pub const ROW_CHANGE_MAX_CYCLES: u32 = 250;
+
+/// The costs above, for checking the timings in picoram-core
+pub const CYCLE_COSTS: CycleCosts = CycleCosts {
+    pin_write: PIN_WRITE_CYCLES,
+    addr_write: ADDR_WRITE_CYCLES,
+    access_max: ACCESS_MAX_CYCLES,
+    row_change_max: ROW_CHANGE_MAX_CYCLES,
+};
+
+/// Longest time from one refresh cycle to the next one, refreshing the rows in turn (ns): that of
+/// the selected [`PART`], or the shortest of all known parts
+pub const T_REFRESH: u32 = match PART {
+    Some(part) => part.part.refresh_interval(),
+    None => chipdb::MIN_REFRESH_INTERVAL,
+};
+
+pub trait DramTimingConfig {
+    /// System clock that the delays are computed for
+    type Clock: SysClock;
+
+    /// Minimum timings from the datasheet, which the delays below are derived from
+    const DATASHEET: NsTimings;
+
+    /// Pulse duration, RAS low (ns)
+    const T_RAS: u32 = Self::DATASHEET.t_ras;
+    /// Pulse duration, CAS low (ns)
+    const T_CAS: u32 = Self::DATASHEET.t_cas;
+    /// RAS low to CAS low delay, longer than tRCD if reads would be limited by tRAC otherwise (ns)
+    const T_RCD: u32 = Self::DATASHEET.ras_to_cas();
+    /// Pulse duration, RAS high (precharge) (ns)
+    const T_RP: u32 = Self::DATASHEET.t_rp;
+    /// Pulse duration, CAS high (precharge) (ns)
+    const T_CP: u32 = Self::DATASHEET.t_cp;
+
+    /// Rest of the RAS pulse after CAS went high again (ns)
+    const T_RAS_REST: u32 = Self::DATASHEET.t_ras_rest();
+    /// Bus transceiver delay (ns)
+    const T_TRANSCEIVER: u32 = TRANSCEIVER_DELAY;
+
+    /// Page mode accesses per RAS pulse, longer bursts are split so that RAS stays low for at most
+    /// tRAS(max) at [`Self::Clock`]. At least 1, see [`Self::ASSERT_VALID`].
+    const MAX_BURST: u32 = core_timings::max_burst(
+        &Self::DATASHEET,
+        <Self::Clock as SysClock>::FREQ,
+        Self::T_TRANSCEIVER,
+        &CYCLE_COSTS,
+    );
+
+    /// Fails the build when evaluated if the reads sample too early for tRAC or tCAC, a cycle keeps
+    /// RAS low for longer than tRAS(max), or the bursts are too long to refresh a row every
+    /// [`T_REFRESH`] at [`Self::Clock`], see [`core_timings::check`] and
+    /// [`core_timings::check_refresh`].
+    const ASSERT_VALID: () = {
+        if let Err(violated) = core_timings::check(
+            &Self::DATASHEET,
+            <Self::Clock as SysClock>::FREQ,
+            Self::T_TRANSCEIVER,
+            &CYCLE_COSTS,
+        ) {
+            panic!("{}", violated);
+        }
+        if let Err(violated) = core_timings::check_refresh(
+            &Self::DATASHEET,
+            <Self::Clock as SysClock>::FREQ,
+            Self::T_TRANSCEIVER,
+            &CYCLE_COSTS,
+            T_REFRESH,
+        ) {
+            panic!("{}", violated);
+        }
+    };
+
+    /// Blocks the program for `ns` nanoseconds at [`Self::Clock`], `ns` has to be a constant.
+    #[inline(always)]
+    fn delay(ns: u32) {
+        delay::delay_ns_const(ns, <Self::Clock as SysClock>::FREQ);
+    }
+
+    /// Blocks the program so that `ns` nanoseconds pass from the last pin edge to the edge made by
+    /// the GPIO writes after the delay, which take `write_cycles` (see [`PIN_WRITE_CYCLES`] and
+    /// [`ADDR_WRITE_CYCLES`]). Both arguments have to be constants.
+    #[inline(always)]
+    fn delay_edges(ns: u32, write_cycles: u32) {
+        delay::delay_edge_to_edge(ns, <Self::Clock as SysClock>::FREQ, write_cycles);
+    }
+}
+
+/// Timings of the selected [`PART`](crate::PART), or of `Default` if no part is selected
+pub struct PartOr<Default>(PhantomData<Default>);
+impl<Default: DramTimingConfig> DramTimingConfig for PartOr<Default> {
+    type Clock = Default::Clock;
+
+    const DATASHEET: NsTimings = match PART {
+        Some(part) => part.grade.timings,
+        None => Default::DATASHEET,
+    };
+}
+
+pub struct Dram150Ns<C = clocks::Fastest>(PhantomData<C>);
+impl<C: SysClock> DramTimingConfig for Dram150Ns<C> {
+    type Clock = C;
+
+    // Timing configuration from TMS4256 datasheet
+    const DATASHEET: NsTimings = core_timings::NS_150;
+}
+
+pub struct Dram120Ns<C = clocks::Fastest>(PhantomData<C>);
+impl<C: SysClock> DramTimingConfig for Dram120Ns<C> {
+    type Clock = C;
+
+    // Timing configuration from TMS4256 datasheet
+    const DATASHEET: NsTimings = core_timings::NS_120;
+}
+
+pub struct Dram100Ns<C = clocks::Fastest>(PhantomData<C>);
+impl<C: SysClock> DramTimingConfig for Dram100Ns<C> {
+    type Clock = C;
+
+    // Timing configuration from TMS4256 datasheet
+    const DATASHEET: NsTimings = core_timings::NS_100;
+}
+
+pub struct Dram80Ns<C = clocks::Fastest>(PhantomData<C>);
+impl<C: SysClock> DramTimingConfig for Dram80Ns<C> {
+    type Clock = C;
+
+    // Timing configuration from TMS4256 datasheet
+    const DATASHEET: NsTimings = core_timings::NS_80;
+}
+
+/// Evaluates `$body` with `$timings` being the [`DramTimingConfig`] of the [`TimingPreset`]
+/// `$preset`, for the [`SysClock`] `$clock`.
+///
+/// All delays have to be known at compile time to be cycle-exact, so `$body` is instantiated once
+/// per preset. Dispatch as rarely as possible (e.g. once per test), not per access.
+macro_rules! with_preset {
+    ($preset:expr, $clock:ty, $timings:ident => $body:expr) => {
+        match $preset {
+            $crate::timings::TimingPreset::Default => {
+                type $timings = $crate::Timings<$clock>;
+                $body
+            }
+            $crate::timings::TimingPreset::Dram150Ns => {
+                type $timings = $crate::timings::Dram150Ns<$clock>;
+                $body
+            }
+            $crate::timings::TimingPreset::Dram120Ns => {
+                type $timings = $crate::timings::Dram120Ns<$clock>;
+                $body
+            }
+            $crate::timings::TimingPreset::Dram100Ns => {
+                type $timings = $crate::timings::Dram100Ns<$clock>;
+                $body
+            }
+            $crate::timings::TimingPreset::Dram80Ns => {
+                type $timings = $crate::timings::Dram80Ns<$clock>;
+                $body
+            }
+        }
+    };
+}
+pub(crate) use with_preset;
+
+/// SRAM timings, which are computed for the [fastest clock](clocks::Fastest), so that they're
+/// never too short.
+pub trait SramTimingConfig {
+    /// Minimum timings from the datasheet, which the delays below are derived from
+    const DATASHEET: SramNsTimings;
+
+    /// Access time, from address valid (with CE and OE low) to data valid (ns)
+    const T_AA: u32 = Self::DATASHEET.t_aa;
+    /// Pulse duration, WE low (ns)
+    const T_WP: u32 = Self::DATASHEET.t_wp;
+    /// Write recovery time, from WE high until the address may change (ns)
+    const T_WR: u32 = Self::DATASHEET.t_wr;
+    /// Bus transceiver delay (ns)
+    const T_TRANSCEIVER: u32 = TRANSCEIVER_DELAY;
+    /// Rest of the address shift register's propagation delay after its RCLK pulse, until the
+    /// address is valid (ns)
+    const T_ADDR_DELAY: u32 = SHIFT_REGISTER_DELAY.saturating_sub(sram::SHIFT_PULSE);
+
+    /// Fails the build when evaluated if the reads sample too early for tOE, see
+    /// [`core_timings::check_sram`].
+    const ASSERT_VALID: () = {
+        if let Err(violated) = core_timings::check_sram(
+            &Self::DATASHEET,
+            <clocks::Fastest as SysClock>::FREQ,
+            Self::T_TRANSCEIVER,
+            Self::T_ADDR_DELAY,
+            &CYCLE_COSTS,
+        ) {
+            panic!("{}", violated);
+        }
+    };
+
+    /// Like [`DramTimingConfig::delay_edges`], at the fastest clock.
+    #[inline(always)]
+    fn delay_edges(ns: u32, write_cycles: u32) {
+        delay::delay_edge_to_edge(ns, <clocks::Fastest as SysClock>::FREQ, write_cycles);
+    }
+}
+
+pub struct Sram150Ns;
+impl SramTimingConfig for Sram150Ns {
+    const DATASHEET: SramNsTimings = core_timings::SRAM_150;
+}
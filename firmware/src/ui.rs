@@ -0,0 +1,903 @@
+//! The user interface (display, LED and buttons), running on core1.
+//!
+//! The test engine on core0 reports to it with [`Event`]s and receives [`Command`]s from it, over
+//! lock-free single-producer single-consumer queues. So tests never wait for the display.
+//!
+//! The status LEDs show the state of the tester with blink codes as well, see [`led::Code`], and
+//! the buzzer sounds when a chip is inserted, taken out, or its result is in, see [`Tone`].
+//!
+//! The USB serial [`Console`] is serviced here as well, it drives the test engine with the same
+//! [`Command`]s as the menu. The events are streamed to it for tools, see [`protocol`].
+//!
+//! Three buttons drive a menu for the [`Settings`] of the test engine: one selects the next entry,
+//! one switches the selected entry to its next value, and one starts or stops the run.
+
+use cortex_m::singleton;
+use embedded_graphics::{
+    mono_font::{self, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::{DrawTarget, Pixel, Point, Primitive, Size},
+    primitives::{PrimitiveStyle, Rectangle},
+    text::{Baseline, Text},
+    Drawable,
+};
+use heapless::spsc::{Consumer, Producer, Queue};
+use ufmt::uwrite;
+
+use crate::{
+    buzzer::{Buzzer, Tone},
+    chipdb::ChipType,
+    clocks::ClockPreset,
+    console::{self, Console, Request},
+    delay::SelfTestError,
+    display::{self, Screen},
+    led::{self, Blinker, StatusLed},
+    march::Test,
+    memory::{AccessTimes, DetectError, FailureMap, MAX_DATA_BITS},
+    progress::{self, Progress},
+    protocol::{self, Message},
+    speedgrade::SpeedGrade,
+    timings::TimingPreset,
+    CLOCK,
+};
+
+/// Capacity of the event queue, plus one
+const EVENT_QUEUE: usize = 8;
+/// Capacity of the command queue, plus one
+const COMMAND_QUEUE: usize = 4;
+
+/// What the test engine reports to the UI.
+// failures carry their map, there's no allocator to box it in
+#[allow(clippy::large_enum_variant)]
+pub enum Event {
+    /// Result of detecting the chip, sent whenever it changes
+    Chip(Result<&'static str, DetectError>),
+    /// Settings of the following passes, sent whenever they change
+    Settings(Settings),
+    Passed {
+        pass_count: u32,
+    },
+    Failed(Failure),
+    Graded(SpeedGrade),
+    AccessTimes(Option<AccessTimes>),
+    /// The delays failed their self-test, the engine refuses to test any further
+    SelfTestFault(SelfTestError),
+}
+
+/// A failed test pass.
+#[derive(Clone)]
+pub struct Failure {
+    pub num_failed_bits: usize,
+    /// The step (march element) that found the failed bits
+    pub step: usize,
+    /// Address of the last failed cell
+    pub addr: usize,
+    pub map: FailureMap,
+    /// Number of failed bits per chip position, all 0 if the memory has no chip positions
+    pub fails_per_chip: [usize; MAX_DATA_BITS + 1],
+}
+
+/// The settings of the test engine, as shown in the menu.
+#[derive(Clone, Copy)]
+pub struct Settings {
+    pub test: Test,
+    /// Expected chip type, if the memory has any
+    pub chip_type: Option<ChipType>,
+    /// Timing preset, if the memory has any
+    pub preset: Option<TimingPreset>,
+    pub clock: ClockPreset,
+    /// Number of passes per run, `None` to run until stopped
+    pub loops: Option<u32>,
+    pub running: bool,
+}
+
+/// An entry of the menu, for one of the [`Settings`]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    Test,
+    Chip,
+    Preset,
+    Clock,
+    Loops,
+}
+
+impl Setting {
+    const ALL: [Setting; 5] = [
+        Setting::Test,
+        Setting::Chip,
+        Setting::Preset,
+        Setting::Clock,
+        Setting::Loops,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Setting::Test => "Test",
+            Setting::Chip => "Chip",
+            Setting::Preset => "Timing",
+            Setting::Clock => "Clock",
+            Setting::Loops => "Loops",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Setting::Test => Setting::Chip,
+            Setting::Chip => Setting::Preset,
+            Setting::Preset => Setting::Clock,
+            Setting::Clock => Setting::Loops,
+            Setting::Loops => Setting::Test,
+        }
+    }
+}
+
+/// What the UI asks the test engine to do, handled between two passes.
+pub enum Command {
+    /// Switch the setting to its next value
+    Next(Setting),
+    /// Start a run, or stop the running one
+    StartStop,
+    /// Start a run of the test
+    Run(Test),
+    Stop,
+    SetPreset(TimingPreset),
+    /// Number of passes per run, `None` until stopped
+    SetLoops(Option<u32>),
+}
+
+/// The test engine's end of the queues.
+pub struct Link {
+    events: Producer<'static, Event, EVENT_QUEUE>,
+    commands: Consumer<'static, Command, COMMAND_QUEUE>,
+}
+
+impl Link {
+    /// Sends `event` to the UI, waiting for it to catch up if the queue is full.
+    pub fn send(&mut self, mut event: Event) {
+        while let Err(e) = self.events.enqueue(event) {
+            event = e;
+        }
+    }
+
+    /// Returns the next command from the UI, if any.
+    pub fn command(&mut self) -> Option<Command> {
+        self.commands.dequeue()
+    }
+
+    /// Waits until the UI sends a command, or `timeout_us` passed. Only polls the queue, so that
+    /// the memory isn't driven while idle.
+    pub fn wait_for_command(&mut self, timeout_us: u32) {
+        let start = progress::now_us();
+        while !self.commands.ready() && progress::now_us().wrapping_sub(start) < timeout_us {}
+    }
+}
+
+pub struct Ui<Led, SelectButton, ChangeButton, StartButton> {
+    display: Screen,
+    led: Blinker<Led>,
+    buzzer: Buzzer,
+    console: Console,
+    char_style: MonoTextStyle<'static, BinaryColor>,
+    select_button_pressed: SelectButton,
+    change_button_pressed: ChangeButton,
+    start_button_pressed: StartButton,
+    events: Consumer<'static, Event, EVENT_QUEUE>,
+    commands: Producer<'static, Command, COMMAND_QUEUE>,
+    /// Latest settings of the test engine
+    settings: Option<Settings>,
+    /// Selected entry while the menu is open
+    menu: Option<Setting>,
+    /// Non-default timing preset and system clock, appended to the results
+    preset_text: heapless::String<16>,
+    /// Latest detected chip
+    chip: Option<&'static str>,
+    /// Latest result of the chip, as shown
+    result_text: heapless::String<64>,
+    /// Latest failure of the chip
+    failure: Option<Failure>,
+}
+
+/// On panels with only 32 rows, results use a smaller font, the chip is shown without a label,
+/// and the progress bar moves to the right of it
+const COMPACT: bool = display::HEIGHT < 64;
+
+const CHIP_TEXT_POS: Point = if COMPACT {
+    Point::zero()
+} else {
+    Point::new(7 * 6, 0)
+};
+
+const TEST_CONTENT_POS: Point = Point::new(0, 13);
+const TEST_CONTENT_SIZE: Size = Size::new(display::WIDTH, display::HEIGHT - 13);
+const TEST_CONTENT_RECT: Rectangle = Rectangle::new(TEST_CONTENT_POS, TEST_CONTENT_SIZE);
+/// Number of menu entries shown at once, one line of FONT_6X10 each
+const MENU_LINES: usize = TEST_CONTENT_SIZE.height as usize / 10;
+
+/// Side of the failure map (pixels), fitting into the test content with a frame around it
+const MAP_PIXELS: u32 = if FailureMap::SIZE as u32 + 2 <= TEST_CONTENT_SIZE.height {
+    FailureMap::SIZE as u32
+} else {
+    TEST_CONTENT_SIZE.height - 2
+};
+/// Failure map, in the top right corner of the test content
+const MAP_POS: Point = Point::new((display::WIDTH - 1 - MAP_PIXELS) as i32, 13 + 1);
+const MAP_FRAME: Rectangle = Rectangle::new(
+    Point::new(MAP_POS.x - 1, MAP_POS.y - 1),
+    Size::new(MAP_PIXELS + 2, MAP_PIXELS + 2),
+);
+
+/// Bottom left of the test content, below three lines of results and next to the failure map. On
+/// compact panels, right of the chip instead, without the text.
+const PROGRESS_POS: Point = if COMPACT {
+    Point::new(64, 0)
+} else {
+    Point::new(0, 13 + 3 * 13)
+};
+const PROGRESS_RECT: Rectangle = if COMPACT {
+    Rectangle::new(PROGRESS_POS, Size::new(display::WIDTH - 64, 13))
+} else {
+    Rectangle::new(
+        PROGRESS_POS,
+        Size::new(MAP_FRAME.top_left.x as u32, 64 - 13 - 3 * 13),
+    )
+};
+const PROGRESS_TEXT_POS: Point = Point::new(PROGRESS_POS.x, PROGRESS_POS.y + 2);
+const PROGRESS_BAR: Rectangle = if COMPACT {
+    Rectangle::new(Point::new(64, 3), Size::new(display::WIDTH - 64, 6))
+} else {
+    Rectangle::new(
+        Point::new(42, 13 + 3 * 13 + 3),
+        Size::new(MAP_FRAME.top_left.x as u32 - 1 - 42, 6),
+    )
+};
+/// Time between redraws of the progress of the running pass (µs)
+const PROGRESS_INTERVAL_US: u32 = 100_000;
+/// The menu closes after this long without a button press (µs)
+const MENU_TIMEOUT_US: u32 = 5_000_000;
+
+impl<Led, SelectButton, ChangeButton, StartButton> Ui<Led, SelectButton, ChangeButton, StartButton>
+where
+    Led: StatusLed,
+    SelectButton: FnMut() -> bool,
+    ChangeButton: FnMut() -> bool,
+    StartButton: FnMut() -> bool,
+{
+    /// Creates the UI along with the test engine's [`Link`] to it. Can only be called once.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        display: Screen,
+        led: Led,
+        buzzer: Buzzer,
+        console: Console,
+        char_style: MonoTextStyle<'static, BinaryColor>,
+        select_button_pressed: SelectButton,
+        change_button_pressed: ChangeButton,
+        start_button_pressed: StartButton,
+    ) -> (Self, Link) {
+        let events = singleton!(: Queue<Event, EVENT_QUEUE> = Queue::new()).unwrap();
+        let commands = singleton!(: Queue<Command, COMMAND_QUEUE> = Queue::new()).unwrap();
+        let (event_producer, event_consumer) = events.split();
+        let (command_producer, command_consumer) = commands.split();
+
+        let ui = Self {
+            display,
+            led: Blinker::new(led),
+            buzzer,
+            console,
+            char_style,
+            select_button_pressed,
+            change_button_pressed,
+            start_button_pressed,
+            events: event_consumer,
+            commands: command_producer,
+            settings: None,
+            menu: None,
+            preset_text: heapless::String::new(),
+            chip: None,
+            result_text: heapless::String::new(),
+            failure: None,
+        };
+        let link = Link {
+            events: event_producer,
+            commands: command_consumer,
+        };
+        (ui, link)
+    }
+
+    /// Shows the events of the test engine and handles the menu forever.
+    ///
+    /// A button counts as pressed when its `…_button_pressed` closure starts returning `true`. The
+    /// select button opens the menu and moves to the next entry, the change button switches the
+    /// selected entry to its next value, and the start button starts or stops the run.
+    pub fn run(mut self) -> ! {
+        let mut select_button_was_pressed = false;
+        let mut change_button_was_pressed = false;
+        let mut start_button_was_pressed = false;
+        let mut last_input_us = 0;
+        let mut last_progress_us = 0;
+
+        loop {
+            let now = progress::now_us();
+
+            let pressed = (self.select_button_pressed)();
+            if pressed && !select_button_was_pressed {
+                self.menu = Some(self.menu.map_or(Setting::ALL[0], Setting::next));
+                self.show_menu();
+                last_input_us = now;
+            }
+            select_button_was_pressed = pressed;
+
+            let pressed = (self.change_button_pressed)();
+            if pressed && !change_button_was_pressed {
+                match self.menu {
+                    // shown again once the engine reports the new settings
+                    Some(setting) => {
+                        let _ = self.commands.enqueue(Command::Next(setting));
+                    }
+                    None => {
+                        self.menu = Some(Setting::ALL[0]);
+                        self.show_menu();
+                    }
+                }
+                last_input_us = now;
+            }
+            change_button_was_pressed = pressed;
+
+            let pressed = (self.start_button_pressed)();
+            if pressed && !start_button_was_pressed {
+                let _ = self.commands.enqueue(Command::StartStop);
+                self.close_menu();
+            }
+            start_button_was_pressed = pressed;
+
+            if self.menu.is_some() && now.wrapping_sub(last_input_us) >= MENU_TIMEOUT_US {
+                self.close_menu();
+            }
+
+            self.led.update(now);
+            self.buzzer.update(now);
+
+            if let Some(request) = self.console.poll() {
+                self.answer(request);
+            }
+
+            if let Some(event) = self.events.dequeue() {
+                self.show(event);
+            } else if let Some(progress) = progress::get() {
+                if now.wrapping_sub(last_progress_us) >= PROGRESS_INTERVAL_US {
+                    self.console.send(&Message::Progress {
+                        step: progress.step as u8,
+                        steps: progress.steps as u8,
+                        row: progress.row,
+                        rows: progress.rows,
+                        elapsed_ms: progress.elapsed_us / 1000,
+                    });
+                    if self.menu.is_none() {
+                        self.show_progress(progress);
+                    }
+                    last_progress_us = now;
+                }
+            }
+        }
+    }
+
+    /// Answers a request from the console, passes it on to the test engine if needed.
+    fn answer(&mut self, request: Request) {
+        let console = &mut self.console;
+        let command = match request {
+            Request::Detect => {
+                let _ = uwrite!(console, "chip: {}\n", self.chip.unwrap_or("?"));
+                None
+            }
+            Request::Run(test) => Some(Command::Run(test)),
+            Request::Stop => Some(Command::Stop),
+            Request::SetTiming(preset) => {
+                if self
+                    .settings
+                    .is_some_and(|settings| settings.preset.is_none())
+                {
+                    let _ = uwrite!(console, "error: no timing presets for this memory\n");
+                    None
+                } else {
+                    Some(Command::SetPreset(preset))
+                }
+            }
+            Request::SetLoops(loops) => Some(Command::SetLoops(loops)),
+            Request::Status => {
+                let _ = uwrite!(console, "chip: {}\n", self.chip.unwrap_or("?"));
+                if let Some(settings) = self.settings {
+                    let _ = uwrite!(console, "test: {}", settings.test.name());
+                    if let Some(chip_type) = settings.chip_type {
+                        let _ = uwrite!(console, ", chip type: {}", chip_type.name());
+                    }
+                    if let Some(preset) = settings.preset {
+                        let _ = uwrite!(console, ", timing: {}", preset.name());
+                    }
+                    let _ = uwrite!(console, ", clock: {}, loops: ", settings.clock.name());
+                    let _ = match settings.loops {
+                        Some(loops) => uwrite!(console, "{}", loops),
+                        None => uwrite!(console, "until stopped"),
+                    };
+                    let state = if settings.running {
+                        "running"
+                    } else {
+                        "stopped"
+                    };
+                    let _ = uwrite!(console, ", {}\n", state);
+                }
+                if !self.result_text.is_empty() {
+                    let _ = uwrite!(console, "last result: {}\n", self.result_text.as_str());
+                }
+                None
+            }
+            Request::Dump => {
+                match &self.failure {
+                    Some(failure) => dump_failure(console, failure),
+                    None => {
+                        let _ = uwrite!(console, "no failure\n");
+                    }
+                }
+                None
+            }
+            Request::Help => {
+                let _ = uwrite!(console, "{}", console::HELP);
+                None
+            }
+        };
+
+        if let Some(command) = command {
+            if self.commands.enqueue(command).is_err() {
+                let _ = uwrite!(console, "error: busy, try again\n");
+            } else {
+                let _ = uwrite!(console, "ok\n");
+            }
+        }
+        console.prompt();
+    }
+
+    /// Shows the failed self-test of the delays and blinks its code forever, like a failed self-test
+    /// at startup. The console is still serviced, but refuses all requests.
+    fn halt(&mut self, err: SelfTestError) -> ! {
+        let mut s = heapless::String::<64>::new();
+        let _ = uwrite!(
+            &mut s,
+            "Delay self-test\nfailed: {} cycles\ninstead of {}",
+            err.measured,
+            err.expected
+        );
+        self.display.clear();
+        Text::with_baseline(&s, Point::zero(), self.char_style, Baseline::Top)
+            .draw(&mut self.display)
+            .unwrap();
+        self.display.flush();
+
+        self.led.set_code(led::Code::SelfTestFault);
+        loop {
+            let now = progress::now_us();
+            self.led.update(now);
+            self.buzzer.update(now);
+            if self.console.poll().is_some() {
+                let _ = uwrite!(&mut self.console, "error: delay self-test failed\n");
+                self.console.prompt();
+            }
+        }
+    }
+
+    /// Shows the result on the LED. Only the first result of a chip (or run) is sounded, the
+    /// passes repeat until the run is stopped.
+    fn set_result(&mut self, passed: bool) {
+        let (code, tone) = if passed {
+            (led::Code::Passed, Tone::Passed)
+        } else {
+            (led::Code::Failed, Tone::Failed)
+        };
+        if self.led.code() != code {
+            self.buzzer.play(tone);
+        }
+        self.led.set_code(code);
+    }
+
+    /// Shows all settings in place of the results, with a marker at the selected one.
+    fn show_menu(&mut self) {
+        let (selected, settings) = match (self.menu, self.settings) {
+            (Some(selected), Some(settings)) => (selected, settings),
+            _ => return,
+        };
+
+        let style = MonoTextStyle::new(&mono_font::ascii::FONT_6X10, BinaryColor::On);
+        // scrolled so that the selected entry is on the last line, if they don't all fit
+        let selected_index = Setting::ALL.iter().position(|&s| s == selected).unwrap();
+        let first = (selected_index + 1).saturating_sub(MENU_LINES);
+
+        let mut s = heapless::String::<128>::new();
+        for &setting in &Setting::ALL[first..] {
+            let marker = if setting == selected { '>' } else { ' ' };
+            let _ = uwrite!(&mut s, "{}{}: ", marker, setting.name());
+            let _ = match setting {
+                Setting::Test => uwrite!(&mut s, "{}", settings.test.name()),
+                Setting::Chip => {
+                    uwrite!(&mut s, "{}", settings.chip_type.map_or("-", ChipType::name))
+                }
+                Setting::Preset => {
+                    uwrite!(
+                        &mut s,
+                        "{}",
+                        settings.preset.map_or("-", TimingPreset::name)
+                    )
+                }
+                Setting::Clock => uwrite!(&mut s, "{}", settings.clock.name()),
+                Setting::Loops => match settings.loops {
+                    Some(loops) => uwrite!(&mut s, "{}", loops),
+                    None => uwrite!(&mut s, "forever"),
+                },
+            };
+            let _ = s.push('\n');
+        }
+
+        self.display
+            .fill_solid(&TEST_CONTENT_RECT, BinaryColor::Off)
+            .unwrap();
+        Text::with_baseline(&s, TEST_CONTENT_POS, style, Baseline::Top)
+            .draw(&mut self.display)
+            .unwrap();
+        self.display.flush();
+    }
+
+    /// Closes the menu, the results of the next pass take its place.
+    fn close_menu(&mut self) {
+        if self.menu.take().is_none() {
+            return;
+        }
+
+        self.display
+            .fill_solid(&TEST_CONTENT_RECT, BinaryColor::Off)
+            .unwrap();
+        self.show_stopped();
+        self.display.flush();
+    }
+
+    /// Replaces the progress with a note if the run is stopped, without flushing.
+    fn show_stopped(&mut self) {
+        if !matches!(self.settings, Some(Settings { running: false, .. })) {
+            return;
+        }
+
+        let style = MonoTextStyle::new(&mono_font::ascii::FONT_5X8, BinaryColor::On);
+        self.display
+            .fill_solid(&PROGRESS_RECT, BinaryColor::Off)
+            .unwrap();
+        Text::with_baseline("Stopped", PROGRESS_TEXT_POS, style, Baseline::Top)
+            .draw(&mut self.display)
+            .unwrap();
+    }
+
+    fn show(&mut self, event: Event) {
+        self.stream(&event);
+        let mut s = heapless::String::<64>::new();
+
+        match event {
+            Event::Chip(detected) => {
+                // a new chip, start over
+                let text = match detected {
+                    Ok(chip) => {
+                        self.led.set_code(led::Code::Testing);
+                        self.buzzer.play(Tone::Detected);
+                        chip
+                    }
+                    Err(DetectError::NoChip) => {
+                        if self.led.code() != led::Code::Idle {
+                            self.buzzer.play(Tone::Removed);
+                        }
+                        self.led.set_code(led::Code::Idle);
+                        "<none>"
+                    }
+                    Err(DetectError::WrongChip) => {
+                        self.led.set_code(led::Code::WrongChip);
+                        self.buzzer.play(Tone::Failed);
+                        "<wrong>"
+                    }
+                };
+
+                self.chip = Some(text);
+                self.result_text.clear();
+                self.failure = None;
+
+                self.display.clear();
+                if !COMPACT {
+                    Text::with_baseline("Chip:", Point::zero(), self.char_style, Baseline::Top)
+                        .draw(&mut self.display)
+                        .unwrap();
+                }
+                Text::with_baseline(text, CHIP_TEXT_POS, self.char_style, Baseline::Top)
+                    .draw(&mut self.display)
+                    .unwrap();
+                self.display.flush();
+                self.show_menu();
+                return;
+            }
+            Event::Settings(settings) => {
+                self.preset_text.clear();
+                if let Some(preset) = settings.preset {
+                    if preset != TimingPreset::Default {
+                        let _ = uwrite!(&mut self.preset_text, " @{}", preset.name());
+                    }
+                }
+                if settings.clock != CLOCK {
+                    let _ = uwrite!(&mut self.preset_text, " {}", settings.clock.name());
+                }
+
+                // a new run, the results of the last one are obsolete
+                let was_running = self.settings.is_some_and(|settings| settings.running);
+                if settings.running && !was_running && self.led.code() != led::Code::WrongChip {
+                    self.led.set_code(led::Code::Testing);
+                }
+                self.settings = Some(settings);
+                if self.menu.is_some() {
+                    self.show_menu();
+                } else {
+                    self.show_stopped();
+                    self.display.flush();
+                }
+                return;
+            }
+            Event::Passed { pass_count } => {
+                self.set_result(true);
+                let _ = uwrite!(&mut s, "PASS #{}{}", pass_count, self.preset_text.as_str());
+            }
+            Event::Failed(failure) => {
+                self.set_result(false);
+                self.result_text.clear();
+                let _ = uwrite!(&mut self.result_text, "FAILS: {}", failure.num_failed_bits);
+                if self.menu.is_none() {
+                    self.show_failure(&failure);
+                }
+                self.failure = Some(failure);
+                return;
+            }
+            Event::Graded(SpeedGrade::Passed(preset)) => {
+                self.set_result(true);
+                let grade = preset.speed_grade().unwrap_or("?");
+                let _ = uwrite!(&mut s, "Grade: {}\n(passes @{})", grade, preset.name());
+            }
+            Event::Graded(SpeedGrade::Failed) => {
+                self.set_result(false);
+                let _ = uwrite!(&mut s, "FAILS @{}", TimingPreset::BY_SPEED[0].name());
+            }
+            Event::Graded(SpeedGrade::Unsupported) => {
+                let _ = uwrite!(&mut s, "No timing presets");
+            }
+            Event::AccessTimes(Some(AccessTimes {
+                t_rac,
+                t_cac,
+                uncertainty,
+            })) => {
+                let _ = uwrite!(
+                    &mut s,
+                    "tRAC: {}ns\ntCAC: {}ns\n(+/-{}ns)",
+                    t_rac,
+                    t_cac,
+                    uncertainty
+                );
+            }
+            Event::AccessTimes(None) => {
+                let _ = uwrite!(&mut s, "No valid data");
+            }
+            Event::SelfTestFault(err) => self.halt(err),
+        }
+
+        self.result_text = s.clone();
+
+        // the results show up on the next pass after the menu is closed
+        if self.menu.is_some() {
+            return;
+        }
+
+        let style = if COMPACT {
+            MonoTextStyle::new(&mono_font::ascii::FONT_6X10, BinaryColor::On)
+        } else {
+            self.char_style
+        };
+        self.display
+            .fill_solid(&TEST_CONTENT_RECT, BinaryColor::Off)
+            .unwrap();
+        Text::with_baseline(&s, TEST_CONTENT_POS, style, Baseline::Top)
+            .draw(&mut self.display)
+            .unwrap();
+        self.display.flush();
+    }
+
+    /// Sends the messages for `event` on the console's stream. Called before the event is shown.
+    fn stream(&mut self, event: &Event) {
+        let message = match *event {
+            Event::Chip(detected) => Message::Chip(match detected {
+                Ok(chip) => protocol::Chip::Detected(chip),
+                Err(DetectError::NoChip) => protocol::Chip::None,
+                Err(DetectError::WrongChip) => protocol::Chip::Wrong,
+            }),
+            Event::Settings(settings) if settings.running => Message::Started {
+                test: settings.test.name(),
+                preset: settings.preset.map(TimingPreset::name),
+                clock_mhz: (settings.clock.freq() / 1_000_000) as u16,
+                loops: settings.loops,
+            },
+            Event::Settings(_) => {
+                if !self.settings.is_some_and(|settings| settings.running) {
+                    return;
+                }
+                Message::Stopped
+            }
+            Event::Passed { pass_count } => {
+                let steps = self
+                    .settings
+                    .map_or(0, |settings| settings.test.elements().len());
+                for step in 0..steps {
+                    self.console.send(&Message::Phase {
+                        step: step as u8,
+                        steps: steps as u8,
+                        failed_bits: 0,
+                    });
+                }
+                Message::Passed { pass_count }
+            }
+            Event::Failed(ref failure) => {
+                let steps = self
+                    .settings
+                    .map_or(0, |settings| settings.test.elements().len());
+                for step in 0..=failure.step {
+                    let failed_bits = if step == failure.step {
+                        failure.num_failed_bits as u32
+                    } else {
+                        0
+                    };
+                    self.console.send(&Message::Phase {
+                        step: step as u8,
+                        steps: steps as u8,
+                        failed_bits,
+                    });
+                }
+                Message::Failed {
+                    failed_bits: failure.num_failed_bits as u32,
+                    last_addr: failure.addr as u32,
+                    fails_per_chip: failure.fails_per_chip.map(|fails| fails as u32),
+                    map_size: FailureMap::SIZE as u8,
+                    map: failure.map.as_bytes(),
+                }
+            }
+            Event::Graded(SpeedGrade::Passed(preset)) => Message::Graded {
+                preset: Some(preset.name()),
+                grade: preset.speed_grade(),
+            },
+            Event::Graded(SpeedGrade::Failed) => Message::Graded {
+                preset: None,
+                grade: None,
+            },
+            Event::Graded(SpeedGrade::Unsupported) => return,
+            Event::SelfTestFault(_) => {
+                if !self.settings.is_some_and(|settings| settings.running) {
+                    return;
+                }
+                Message::Stopped
+            }
+            Event::AccessTimes(ref access_times) => {
+                Message::AccessTimes(access_times.as_ref().map(|times| protocol::AccessTimes {
+                    t_rac_ns: times.t_rac,
+                    t_cac_ns: times.t_cac,
+                    uncertainty_ns: times.uncertainty,
+                }))
+            }
+        };
+        self.console.send(&message);
+    }
+
+    /// Shows a short summary of the failure next to the map of the failed cells.
+    fn show_failure(&mut self, failure: &Failure) {
+        // smaller font, to leave room for the map
+        let style = MonoTextStyle::new(&mono_font::ascii::FONT_6X10, BinaryColor::On);
+        let mut s = heapless::String::<64>::new();
+        let _ = uwrite!(&mut s, "FAILS: {}\n", failure.num_failed_bits);
+
+        // on modules, point to the chips to replace instead of the address
+        if failure.fails_per_chip.iter().all(|&fails| fails == 0) {
+            let _ = uwrite!(&mut s, "Last: {:X}", failure.addr);
+        } else {
+            let _ = uwrite!(&mut s, "Bad:");
+            for (pos, &fails) in failure.fails_per_chip.iter().enumerate() {
+                if fails > 0 {
+                    let _ = uwrite!(&mut s, " U{}", pos);
+                }
+            }
+        }
+        let _ = uwrite!(&mut s, "\n{}", self.preset_text.trim_start());
+
+        self.display
+            .fill_solid(&TEST_CONTENT_RECT, BinaryColor::Off)
+            .unwrap();
+        Text::with_baseline(&s, TEST_CONTENT_POS, style, Baseline::Top)
+            .draw(&mut self.display)
+            .unwrap();
+
+        // drawn last, so that long lines of text can't cover it
+        self.display
+            .fill_solid(&MAP_FRAME, BinaryColor::Off)
+            .unwrap();
+        MAP_FRAME
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+            .draw(&mut self.display)
+            .unwrap();
+        let map = &failure.map;
+        let failed = (0..FailureMap::SIZE)
+            .flat_map(|r| (0..FailureMap::SIZE).map(move |c| (r, c)))
+            .filter(|&(r, c)| map.failed(r, c))
+            .map(|(r, c)| {
+                // scaled down further if the map doesn't fit
+                let x = c * MAP_PIXELS as usize / FailureMap::SIZE;
+                let y = r * MAP_PIXELS as usize / FailureMap::SIZE;
+                Pixel(MAP_POS + Point::new(x as i32, y as i32), BinaryColor::On)
+            });
+        self.display.draw_iter(failed).unwrap();
+        self.display.flush();
+    }
+
+    /// Shows the current step, the elapsed time and a progress bar below the results (only the bar on
+    /// compact panels).
+    fn show_progress(&mut self, progress: Progress) {
+        let style = MonoTextStyle::new(&mono_font::ascii::FONT_5X8, BinaryColor::On);
+        let mut s = heapless::String::<16>::new();
+        let _ = uwrite!(
+            &mut s,
+            "{}/{} {}s",
+            progress.step + 1,
+            progress.steps,
+            progress.elapsed_us / 1_000_000
+        );
+
+        self.display
+            .fill_solid(&PROGRESS_RECT, BinaryColor::Off)
+            .unwrap();
+        if !COMPACT {
+            Text::with_baseline(&s, PROGRESS_TEXT_POS, style, Baseline::Top)
+                .draw(&mut self.display)
+                .unwrap();
+        }
+        PROGRESS_BAR
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+            .draw(&mut self.display)
+            .unwrap();
+        let done = Size::new(
+            progress.scaled(PROGRESS_BAR.size.width),
+            PROGRESS_BAR.size.height,
+        );
+        self.display
+            .fill_solid(
+                &Rectangle::new(PROGRESS_BAR.top_left, done),
+                BinaryColor::On,
+            )
+            .unwrap();
+        self.display.flush();
+    }
+}
+
+/// Writes the details of `failure` and its map to `console`, one character per bin of the map.
+fn dump_failure(console: &mut Console, failure: &Failure) {
+    let _ = uwrite!(
+        console,
+        "{} broken bits, last at address {:X}\n",
+        failure.num_failed_bits,
+        failure.addr
+    );
+    for (pos, &fails) in failure.fails_per_chip.iter().enumerate() {
+        if fails > 0 {
+            let _ = uwrite!(console, "U{}: {} broken bits\n", pos, fails);
+        }
+    }
+
+    let _ = uwrite!(console, "map, rows down, columns right, # = failed:\n");
+    for r in 0..FailureMap::SIZE {
+        let mut line = heapless::String::<{ FailureMap::SIZE + 1 }>::new();
+        for c in 0..FailureMap::SIZE {
+            let _ = line.push(if failure.map.failed(r, c) { '#' } else { '.' });
+        }
+        let _ = line.push('\n');
+        let _ = uwrite!(console, "{}", line.as_str());
+    }
+}
@@ -36,4 +36,15 @@ SECTIONS {
     {
         KEEP(*(.boot2));
     } > BOOT2
-} INSERT BEFORE .text;
\ No newline at end of file
+} INSERT BEFORE .text;
+
+SECTIONS {
+    /* ### Timing-critical code
+     * Run from RAM, so that it isn't stalled by flash XIP cache misses. Copied to RAM at startup
+     * along with .data. Check that it doesn't call into flash with `check-ram-text.sh`. */
+    .ram_text : ALIGN(4)
+    {
+        *(.ram_text .ram_text.*);
+        . = ALIGN(4);
+    } > RAM AT>FLASH
+} INSERT AFTER .data;
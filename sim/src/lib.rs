@@ -0,0 +1,217 @@
+//! Simulated memories for running the test algorithms of [`picoram_core`] on the host, with faults
+//! injected into them.
+//!
+//! The cells are addressed as `(row, col)`, like the algorithms see them through
+//! [`MemoryUnderTest`].
+
+use picoram_core::{
+    memory::{DetectError, MemoryUnderTest, MAX_DATA_BITS},
+    timings::TimingPreset,
+};
+
+/// A fault of the simulated memory
+#[derive(Clone, Copy, Debug)]
+pub enum Fault {
+    /// Data bit `bit` of the cell always reads as `value`
+    StuckAt {
+        cell: (usize, usize),
+        bit: u8,
+        value: bool,
+    },
+    /// Inversion coupling fault: every transition of data bit `bit` of the `aggressor` inverts
+    /// that bit of the `victim`
+    Coupling {
+        aggressor: (usize, usize),
+        victim: (usize, usize),
+        bit: u8,
+    },
+    /// Address decoder fault: writes to row `row` also end up in row `alias`
+    RowAlias { row: usize, alias: usize },
+}
+
+/// A simulated DRAM, a single chip or a module of several
+pub struct SimDram {
+    name: &'static str,
+    rows: usize,
+    cols: usize,
+    data_bits: u8,
+    cells: Vec<u32>,
+    open_row: Option<usize>,
+    faults: Vec<Fault>,
+    /// Whether the chip is inserted
+    present: bool,
+    preset: TimingPreset,
+    /// The fastest timing preset the chip works with, all reads are garbage with faster ones
+    fastest: TimingPreset,
+}
+
+impl SimDram {
+    /// A 4164, 64K × 1
+    pub fn dram4164() -> Self {
+        Self::new("4164", 256, 256, 1)
+    }
+
+    /// A 41256, 256K × 1
+    pub fn dram41256() -> Self {
+        Self::new("41256", 512, 512, 1)
+    }
+
+    /// A 30-pin 256K × 9 SIMM, with one chip per data bit
+    pub fn simm256k9() -> Self {
+        Self::new("256K SIMM", 512, 512, 9)
+    }
+
+    /// A memory of `rows` × `cols` words of `data_bits` bits, initially all 0 and without faults
+    pub fn new(name: &'static str, rows: usize, cols: usize, data_bits: u8) -> Self {
+        assert!(rows.is_power_of_two() && cols.is_power_of_two());
+        assert!((1..=MAX_DATA_BITS as u8).contains(&data_bits));
+        Self {
+            name,
+            rows,
+            cols,
+            data_bits,
+            cells: vec![0; rows * cols],
+            open_row: None,
+            faults: Vec::new(),
+            present: true,
+            preset: TimingPreset::Default,
+            fastest: TimingPreset::Dram80Ns,
+        }
+    }
+
+    pub fn with_fault(mut self, fault: Fault) -> Self {
+        self.faults.push(fault);
+        self
+    }
+
+    /// Makes the chip fail with timing presets faster than `fastest`.
+    pub fn with_fastest_preset(mut self, fastest: TimingPreset) -> Self {
+        self.fastest = fastest;
+        self
+    }
+
+    /// Takes the chip out of the socket, or puts it back.
+    pub fn set_present(&mut self, present: bool) {
+        self.present = present;
+    }
+
+    fn mask(&self) -> u32 {
+        u32::MAX >> (32 - u32::from(self.data_bits))
+    }
+
+    fn index(&self, (row, col): (usize, usize)) -> usize {
+        row * self.cols + col
+    }
+
+    /// Whether the timing preset is too fast for the chip. `Default` is the slowest.
+    fn too_fast(&self) -> bool {
+        let speed = |preset| {
+            TimingPreset::BY_SPEED
+                .iter()
+                .position(|&p| p == preset)
+                .map_or(0, |i| i + 1)
+        };
+        speed(self.preset) > speed(self.fastest)
+    }
+
+    fn store(&mut self, cell: (usize, usize), word: u32) {
+        let index = self.index(cell);
+        let old = std::mem::replace(&mut self.cells[index], word);
+        for i in 0..self.faults.len() {
+            if let Fault::Coupling {
+                aggressor,
+                victim,
+                bit,
+            } = self.faults[i]
+            {
+                if aggressor == cell && (old ^ word) & (1 << bit) != 0 {
+                    let victim = self.index(victim);
+                    self.cells[victim] ^= 1 << bit;
+                }
+            }
+        }
+    }
+
+    fn row(&self) -> usize {
+        self.open_row.expect("no row is open")
+    }
+}
+
+impl MemoryUnderTest for SimDram {
+    fn detect(&mut self) -> Result<&'static str, DetectError> {
+        match self.present {
+            true => Ok(self.name),
+            false => Err(DetectError::NoChip),
+        }
+    }
+
+    fn rows(&self) -> usize {
+        self.rows
+    }
+
+    fn cols(&self) -> usize {
+        self.cols
+    }
+
+    fn data_bits(&self) -> u8 {
+        self.data_bits
+    }
+
+    fn chip_position(&self, bit: u8) -> Option<u8> {
+        (self.data_bits > 1).then_some(bit + 1)
+    }
+
+    fn open_row(&mut self, row: usize) {
+        assert!(
+            self.open_row.is_none(),
+            "row {row} opened before closing the last one"
+        );
+        self.open_row = Some(row);
+    }
+
+    fn close_row(&mut self) {
+        self.open_row = None;
+    }
+
+    fn write(&mut self, col: usize, word: u32) {
+        let row = self.row();
+        let word = word & self.mask();
+        self.store((row, col), word);
+        for i in 0..self.faults.len() {
+            if let Fault::RowAlias { row: r, alias } = self.faults[i] {
+                if r == row {
+                    self.store((alias, col), word);
+                }
+            }
+        }
+    }
+
+    fn read(&mut self, col: usize) -> u32 {
+        let cell = (self.row(), col);
+        let mut word = self.cells[self.index(cell)];
+        for fault in &self.faults {
+            if let Fault::StuckAt {
+                cell: c,
+                bit,
+                value,
+            } = *fault
+            {
+                if c == cell {
+                    word = (word & !(1 << bit)) | (u32::from(value) << bit);
+                }
+            }
+        }
+        if self.too_fast() {
+            word = !word & self.mask();
+        }
+        word
+    }
+
+    fn set_timing_preset(&mut self, preset: TimingPreset) {
+        self.preset = preset;
+    }
+
+    fn timing_preset(&self) -> Option<TimingPreset> {
+        Some(self.preset)
+    }
+}
@@ -0,0 +1,125 @@
+//! The test algorithms against simulated memories with known faults.
+
+use picoram_core::{
+    march::{self, Test},
+    memory::{FailureMap, MemoryUnderTest},
+    speedgrade::{self, SpeedGrade},
+    timings::TimingPreset,
+};
+use picoram_sim::{Fault, SimDram};
+
+#[test]
+fn good_chips_pass() {
+    for test in Test::ALL {
+        assert!(SimDram::dram4164().run_test(test.elements()).is_ok());
+        assert!(SimDram::simm256k9().run_test(test.elements()).is_ok());
+    }
+}
+
+#[test]
+fn stuck_at_is_found() {
+    for test in Test::ALL {
+        for value in [false, true] {
+            let mut mem = SimDram::dram41256().with_fault(Fault::StuckAt {
+                cell: (300, 17),
+                bit: 0,
+                value,
+            });
+            let Err(err) = mem.run_test(test.elements()) else {
+                panic!("{} missed a bit stuck at {value}", test.name());
+            };
+            assert_eq!((err.row, err.col), (300, 17));
+            assert_eq!(err.num_failed_bits, 1);
+            assert_eq!(err.fails_per_bit[0], 1);
+
+            // 512 rows and columns into 46 bins
+            let bin = |i: usize| i * FailureMap::SIZE / 512;
+            for r in 0..FailureMap::SIZE {
+                for c in 0..FailureMap::SIZE {
+                    assert_eq!(err.map.failed(r, c), (r, c) == (bin(300), bin(17)));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn failed_step_is_reported() {
+    // the background is all 1s, MovInv first reads its inverse in the third step
+    let mut mem = SimDram::dram4164().with_fault(Fault::StuckAt {
+        cell: (0, 0),
+        bit: 0,
+        value: true,
+    });
+    let err = mem.run_test(march::MOVING_INVERSIONS).unwrap_err();
+    assert_eq!(err.step, 2);
+}
+
+/// Writes `word` everywhere.
+fn fill(mem: &mut SimDram, word: u32) {
+    for row in 0..mem.rows() {
+        mem.open_row(row);
+        for col in 0..mem.cols() {
+            mem.write(col, word);
+        }
+        mem.close_row();
+    }
+}
+
+#[test]
+fn coupling_needs_march_c() {
+    // MATS+ only catches inversion coupling faults of aggressors below the victim
+    let fault = Fault::Coupling {
+        aggressor: (200, 3),
+        victim: (100, 5),
+        bit: 0,
+    };
+    let mut mem = SimDram::dram4164();
+    // already the background, so that the first element doesn't flip the victim
+    fill(&mut mem, 1);
+    let mut mem = mem.with_fault(fault);
+    assert!(mem.run_test(march::MATS_PLUS).is_ok());
+
+    let mut mem = SimDram::dram4164();
+    fill(&mut mem, 1);
+    let err = mem
+        .with_fault(fault)
+        .run_test(march::MARCH_C_MINUS)
+        .unwrap_err();
+    assert_eq!((err.row, err.col), (100, 5));
+}
+
+#[test]
+fn row_alias_is_found() {
+    let mut mem = SimDram::dram4164().with_fault(Fault::RowAlias { row: 10, alias: 20 });
+    assert!(mem.run_test(march::MATS_PLUS).is_err());
+}
+
+#[test]
+fn fails_per_bit_of_modules() {
+    let mut mem = SimDram::simm256k9().with_fault(Fault::StuckAt {
+        cell: (7, 8),
+        bit: 8,
+        value: true,
+    });
+    let err = mem.run_test(march::MARCH_C_MINUS).unwrap_err();
+    assert_eq!(err.fails_per_bit, [0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    assert_eq!(mem.chip_position(8), Some(9));
+}
+
+#[test]
+fn speed_grade() {
+    let mut mem = SimDram::dram4164().with_fastest_preset(TimingPreset::Dram100Ns);
+    assert_eq!(
+        speedgrade::grade(&mut mem),
+        SpeedGrade::Passed(TimingPreset::Dram100Ns)
+    );
+    assert_eq!(mem.timing_preset(), Some(TimingPreset::Default));
+
+    let mut mem = SimDram::dram4164().with_fault(Fault::StuckAt {
+        cell: (1, 1),
+        bit: 0,
+        value: true,
+    });
+    assert_eq!(speedgrade::grade(&mut mem), SpeedGrade::Failed);
+}
@@ -0,0 +1,73 @@
+//! The progress of a pass, in a test binary of its own: it's global, so the tests in `march.rs`
+//! would change it in parallel.
+
+use picoram_core::{
+    march::Test,
+    memory::{DetectError, MemoryUnderTest},
+    progress::{self, Progress},
+};
+use picoram_sim::SimDram;
+
+/// Takes a snapshot of the progress whenever a row is opened.
+struct Watched {
+    mem: SimDram,
+    seen: Vec<Progress>,
+}
+
+impl MemoryUnderTest for Watched {
+    fn detect(&mut self) -> Result<&'static str, DetectError> {
+        self.mem.detect()
+    }
+
+    fn rows(&self) -> usize {
+        self.mem.rows()
+    }
+
+    fn cols(&self) -> usize {
+        self.mem.cols()
+    }
+
+    fn data_bits(&self) -> u8 {
+        self.mem.data_bits()
+    }
+
+    fn open_row(&mut self, row: usize) {
+        self.seen.push(progress::get().unwrap());
+        self.mem.open_row(row);
+    }
+
+    fn close_row(&mut self) {
+        self.mem.close_row();
+    }
+
+    fn write(&mut self, col: usize, word: u32) {
+        self.mem.write(col, word);
+    }
+
+    fn read(&mut self, col: usize) -> u32 {
+        self.mem.read(col)
+    }
+}
+
+#[test]
+fn progress_follows_the_pass() {
+    assert!(progress::get().is_none());
+
+    let test = Test::MarchCMinus.elements();
+    let mut mem = Watched {
+        mem: SimDram::dram4164(),
+        seen: Vec::new(),
+    };
+    assert!(mem.run_test(test).is_ok());
+
+    // a row at a time, through all elements
+    assert_eq!(mem.seen.len(), test.len() * 256);
+    for (i, progress) in mem.seen.iter().enumerate() {
+        assert_eq!((progress.steps, progress.rows), (test.len() as u32, 256));
+        assert_eq!(
+            (progress.step, progress.row),
+            ((i / 256) as u32, (i % 256) as u32)
+        );
+    }
+    assert!(progress::get().is_none());
+}